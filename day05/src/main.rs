@@ -1,4 +1,6 @@
+use std::env;
 use std::io::{self, Read};
+use std::ops::RangeInclusive;
 
 const NUM_COLS: u32 = 8;
 const NUM_ROWS: u32 = 128;
@@ -21,18 +23,74 @@ impl Seat {
     }
 }
 
-fn find_missing_seat_id(seats: &[Seat]) -> Option<u32> {
-    let mut ids: Vec<_> = seats.iter().map(|s| s.id()).collect();
-    ids.sort_unstable();
+struct SeatMap {
+    occupied: Vec<bool>,
+}
 
-    let mut first = ids[0];
-    for id in ids {
-        if (id - first) > 1 {
-            return Some(id - 1);
+impl SeatMap {
+    fn from_seats(seats: &[Seat]) -> SeatMap {
+        let mut occupied = vec![false; (NUM_ROWS * NUM_COLS) as usize];
+        for seat in seats {
+            occupied[seat.id() as usize] = true;
         }
-        first = id;
+        return SeatMap {occupied};
+    }
+
+    fn is_occupied(&self, id: u32) -> bool {
+        return self.occupied.get(id as usize).copied().unwrap_or(false);
+    }
+
+    fn empty_seats(&self) -> Vec<u32> {
+        return (0..self.occupied.len() as u32)
+            .filter(|&id| !self.is_occupied(id))
+            .collect();
+    }
+
+    fn empty_runs(&self) -> Vec<RangeInclusive<u32>> {
+        let mut runs = Vec::new();
+        let mut start = None;
+        for id in 0..self.occupied.len() as u32 {
+            match (self.is_occupied(id), start) {
+                (false, None) => start = Some(id),
+                (true, Some(s)) => {
+                    runs.push(s..=(id - 1));
+                    start = None;
+                },
+                _ => (),
+            }
+        }
+        if let Some(s) = start {
+            runs.push(s..=(self.occupied.len() as u32 - 1));
+        }
+        return runs;
+    }
+
+    fn isolated_gaps(&self) -> Vec<u32> {
+        return self.empty_seats().into_iter().filter(|&id| {
+            id > 0 && self.is_occupied(id - 1) && self.is_occupied(id + 1)
+        }).collect();
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::from("    ");
+        for col in 0..NUM_COLS {
+            out.push_str(&col.to_string());
+        }
+        out.push('\n');
+        for row in 0..NUM_ROWS {
+            out.push_str(&format!("{:3} ", row));
+            for col in 0..NUM_COLS {
+                let id = (row * NUM_COLS) + col;
+                out.push(if self.is_occupied(id) { '#' } else { '.' });
+            }
+            out.push('\n');
+        }
+        return out;
     }
-    return None;
+}
+
+fn find_missing_seat_id(seats: &[Seat]) -> Option<u32> {
+    return SeatMap::from_seats(seats).isolated_gaps().first().copied();
 }
 
 fn get_axis(seat: &[char], min: u32, max: u32) -> u32 {
@@ -56,6 +114,15 @@ fn part2(seats: &[Seat]) {
     println!("My seat ID: {}", my_id);
 }
 
+fn print_map(seats: &[Seat]) {
+    let map = SeatMap::from_seats(seats);
+    print!("{}", map.render());
+    for run in map.empty_runs() {
+        println!("Empty seats: {}-{}", run.start(), run.end());
+    }
+    println!("Isolated gaps: {:?}", map.isolated_gaps());
+}
+
 fn main() {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
@@ -63,6 +130,10 @@ fn main() {
 
     part1(&seats);
     part2(&seats);
+
+    if env::args().any(|a| a == "--map") {
+        print_map(&seats);
+    }
 }
 
 #[cfg(test)]
@@ -116,4 +187,31 @@ mod tests {
         assert_eq!(seat.row, 102);
         assert_eq!(seat.col, 4);
     }
+
+    #[test]
+    fn test_find_missing_seat_id() {
+        let seats = vec![Seat {row: 1, col: 0}, Seat {row: 1, col: 2}];
+        assert_eq!(find_missing_seat_id(&seats), Some(9));
+        assert_eq!(find_missing_seat_id(&[]), None);
+    }
+
+    #[test]
+    fn test_seat_map_empty_runs() {
+        let seats: Vec<_> = (2..NUM_ROWS * NUM_COLS - 3)
+            .filter(|&id| id != 10 && id != 11)
+            .map(|id| Seat {row: id / NUM_COLS, col: id % NUM_COLS})
+            .collect();
+        let map = SeatMap::from_seats(&seats);
+        let last = NUM_ROWS * NUM_COLS - 1;
+        assert_eq!(map.empty_runs(), vec![0..=1, 10..=11, (last - 2)..=last]);
+        assert_eq!(map.empty_seats().len(), 7);
+        assert!(map.isolated_gaps().is_empty());
+    }
+
+    #[test]
+    fn test_seat_map_render() {
+        let map = SeatMap::from_seats(&[Seat {row: 0, col: 1}, Seat {row: 1, col: 7}]);
+        let lines: Vec<_> = map.render().lines().take(3).map(|l| l.to_owned()).collect();
+        assert_eq!(lines, vec!["    01234567", "  0 .#......", "  1 .......#"]);
+    }
 }
@@ -1,61 +1,322 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
 use std::io::{self, Read};
+use std::process;
 
-type GroupParser = fn(&str) -> Vec<char>;
+const NUM_QUESTIONS: u8 = 26;
 
-fn parse_group(group: &str) -> Vec<char> {
-    let mut answers = group.lines().map(|p| parse_person(p))
-        .collect::<Vec<_>>()
-        .concat();
-    answers.sort();
-    answers.dedup();
-    return answers;
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    UnexpectedChar(char),
 }
 
-fn parse_group_all(group: &str) -> Vec<char> {
-    let people: Vec<_> = group.lines().map(|p| parse_person(p)).collect();
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedChar(c) => write!(f, "Unexpected character: {:?}", c),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Answers(u32);
+
+fn question_bit(question: char) -> Option<u32> {
+    return match question {
+        'a'..='z' => Some(1 << (question as u8 - b'a')),
+        _ => None,
+    };
+}
+
+impl Answers {
+    /// Whitespace, like the `\r` left over from CRLF input, is skipped
+    fn from_chars(chars: impl Iterator<Item = char>) -> Result<Answers, ParseError> {
+        let mut bits = 0;
+        for c in chars.filter(|c| !c.is_whitespace()) {
+            bits |= question_bit(c).ok_or(ParseError::UnexpectedChar(c))?;
+        }
+        return Ok(Answers(bits));
+    }
+
+    fn contains(&self, question: char) -> bool {
+        return match question_bit(question) {
+            Some(bit) => self.0 & bit != 0,
+            None => false,
+        };
+    }
+
+    fn len(&self) -> usize {
+        return self.0.count_ones() as usize;
+    }
+
+    fn questions(&self) -> Vec<char> {
+        return questions().filter(|q| self.contains(*q)).collect();
+    }
+}
+
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.questions().into_iter().collect::<String>());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Query {
+    Union,
+    Intersection,
+    AtLeast(usize),
+    ExactlyOne,
+    SymmetricDifference,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Group {
+    people: Vec<Answers>,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        return self.people.len();
+    }
+
+    fn count(&self, question: char) -> usize {
+        return self.people.iter().filter(|p| p.contains(question)).count();
+    }
+
+    fn query(&self, query: Query) -> Answers {
+        return match query {
+            Query::Union => Answers(self.people.iter().fold(0, |acc, p| acc | p.0)),
+            Query::Intersection => match self.people.split_first() {
+                None => Answers::default(),
+                Some((first, rest)) => Answers(rest.iter().fold(first.0, |acc, p| acc & p.0)),
+            },
+            Query::SymmetricDifference => Answers(self.people.iter().fold(0, |acc, p| acc ^ p.0)),
+            Query::AtLeast(k) => self.filter_counts(|n| n >= k),
+            Query::ExactlyOne => self.filter_counts(|n| n == 1),
+        };
+    }
+
+    fn filter_counts<F: Fn(usize) -> bool>(&self, predicate: F) -> Answers {
+        let bits = questions()
+            .filter(|q| predicate(self.count(*q)))
+            .filter_map(question_bit)
+            .fold(0, |acc, bit| acc | bit);
+        return Answers(bits);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct QuestionStats {
+    question: char,
+    people: usize,
+    groups: usize,
+    unanimous: usize,
+}
+
+fn question_stats(groups: &[Group]) -> Vec<QuestionStats> {
+    return questions().map(|question| {
+        let counts: Vec<_> = groups.iter().map(|g| (g.count(question), g.size())).collect();
+        QuestionStats {
+            question,
+            people: counts.iter().map(|(n, _)| n).sum(),
+            groups: counts.iter().filter(|(n, _)| *n > 0).count(),
+            unanimous: counts.iter().filter(|(n, size)| *n > 0 && n == size).count(),
+        }
+    }).collect();
+}
+
+#[derive(Debug, PartialEq)]
+struct Report {
+    groups: usize,
+    people: usize,
+    questions: Vec<QuestionStats>,
+    group_sizes: BTreeMap<usize, usize>,
+    unanimous_rate: f64,
+    largest_group: Option<(usize, usize)>,
+    smallest_group: Option<(usize, usize)>,
+}
+
+impl Report {
+    fn from_groups(groups: &[Group]) -> Report {
+        let mut group_sizes = BTreeMap::new();
+        for group in groups {
+            *group_sizes.entry(group.size()).or_insert(0) += 1;
+        }
+
+        let answered = sum_query(groups, Query::Union);
+        let unanimous = sum_query(groups, Query::Intersection);
+        let unanimous_rate = match answered {
+            0 => 0.0,
+            _ => unanimous as f64 / answered as f64,
+        };
+
+        let sizes = groups.iter().map(|g| g.size()).enumerate();
+        // Ties go to the earliest group, so reverse the index ordering for max
+        let largest_group = sizes.clone().max_by_key(|&(i, size)| (size, usize::MAX - i));
+        let smallest_group = sizes.min_by_key(|&(i, size)| (size, i));
 
-    return people.first().unwrap().iter().filter(|c| {
-        people.iter().all(|p| p.contains(c))
-    }).map(|c| c.to_owned()).collect()
+        return Report {
+            groups: groups.len(),
+            people: groups.iter().map(|g| g.size()).sum(),
+            questions: question_stats(groups),
+            group_sizes,
+            unanimous_rate,
+            largest_group,
+            smallest_group,
+        };
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("Groups: {}\n", self.groups));
+        out.push_str(&format!("People: {}\n", self.people));
+        out.push_str(&format!("Unanimous question rate: {:.4}\n", self.unanimous_rate));
+        if let Some((index, size)) = self.largest_group {
+            out.push_str(&format!("Largest group: #{} ({} people)\n", index, size));
+        }
+        if let Some((index, size)) = self.smallest_group {
+            out.push_str(&format!("Smallest group: #{} ({} people)\n", index, size));
+        }
+        out.push_str("Group sizes:\n");
+        for (size, count) in &self.group_sizes {
+            out.push_str(&format!("  {}: {}\n", size, count));
+        }
+        out.push_str("Questions:\n");
+        for stats in &self.questions {
+            out.push_str(&format!("  {}: {} people, {} groups, {} unanimous\n",
+                stats.question, stats.people, stats.groups, stats.unanimous));
+        }
+        return out;
+    }
+
+    fn to_json(&self) -> String {
+        let group_json = |group: Option<(usize, usize)>| match group {
+            Some((index, size)) => format!("{{\"index\":{},\"size\":{}}}", index, size),
+            None => String::from("null"),
+        };
+        let sizes: Vec<_> = self.group_sizes.iter()
+            .map(|(size, count)| format!("\"{}\":{}", size, count))
+            .collect();
+        let questions: Vec<_> = self.questions.iter().map(|q| {
+            format!("{{\"question\":\"{}\",\"people\":{},\"groups\":{},\"unanimous\":{}}}",
+                q.question, q.people, q.groups, q.unanimous)
+        }).collect();
+
+        return format!(
+            "{{\"groups\":{},\"people\":{},\"unanimous_rate\":{},\"largest_group\":{},\"smallest_group\":{},\"group_sizes\":{{{}}},\"questions\":[{}]}}",
+            self.groups,
+            self.people,
+            self.unanimous_rate,
+            group_json(self.largest_group),
+            group_json(self.smallest_group),
+            sizes.join(","),
+            questions.join(","),
+        );
+    }
 }
 
-fn parse_input(input: &str, parser: GroupParser) -> Vec<Vec<char>> {
-    return input.split("\n\n").map(|g| parser(g)).collect();
+fn questions() -> impl Iterator<Item = char> {
+    return (0..NUM_QUESTIONS).map(|i| (b'a' + i) as char);
 }
 
-fn parse_person(person: &str) -> Vec<char> {
-    return person.chars().collect();
+fn parse_group(group: &str) -> Result<Group, ParseError> {
+    let people = group.lines().map(|p| parse_person(p)).collect::<Result<_, _>>()?;
+    return Ok(Group {people});
 }
 
-fn part1(input: &str) {
-    let groups = parse_input(&input, parse_group);
-    let answer: usize = groups.iter().map(|g| g.len()).sum();
+fn parse_input(input: &str) -> Result<Vec<Group>, ParseError> {
+    return input.replace("\r\n", "\n").split("\n\n").map(|g| parse_group(g)).collect();
+}
+
+fn parse_person(person: &str) -> Result<Answers, ParseError> {
+    return Answers::from_chars(person.chars());
+}
+
+fn sum_query(groups: &[Group], query: Query) -> usize {
+    return groups.iter().map(|g| g.query(query).len()).sum();
+}
+
+fn part1(groups: &[Group]) {
+    let answer = sum_query(groups, Query::Union);
     println!("Answer for part 1: {}", answer);
 }
 
-fn part2(input: &str) {
-    let groups = parse_input(&input, parse_group_all);
-    let answer: usize = groups.iter().map(|g| g.len()).sum();
+fn part2(groups: &[Group]) {
+    let answer = sum_query(groups, Query::Intersection);
     println!("Answer for part 2: {}", answer);
 }
 
+fn print_stats(groups: &[Group]) {
+    println!("At least two people: {}", sum_query(groups, Query::AtLeast(2)));
+    println!("Exactly one person: {}", sum_query(groups, Query::ExactlyOne));
+    println!("Symmetric difference: {}", sum_query(groups, Query::SymmetricDifference));
+}
+
 fn main() {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
+    let groups = match parse_input(&input) {
+        Ok(groups) => groups,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    };
+
+    let args: Vec<_> = env::args().skip(1).collect();
+    // Nothing else goes to stdout alongside the JSON, so it can be piped on
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", Report::from_groups(&groups).to_json());
+        return;
+    }
 
-    part1(&input);
-    part2(&input);
+    part1(&groups);
+    part2(&groups);
+    if args.iter().any(|a| a == "--stats") {
+        print_stats(&groups);
+    }
+    if args.iter().any(|a| a == "--report") {
+        print!("{}", Report::from_groups(&groups).to_text());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const INPUT: &str = "abc
+
+a
+b
+c
+
+ab
+ac
+
+a
+a
+a
+a
+
+b";
+
     #[test]
     fn test_parse_line() {
         let expected = vec!['a', 'b', 'c', 'x'];
-        let actual = parse_person("abcx");
-        assert_eq!(actual, expected);
+        let actual = parse_person("abcx").unwrap();
+        assert_eq!(actual.questions(), expected);
+        assert_eq!(actual.to_string(), "abcx");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_person("ab c\r").unwrap().questions(), vec!['a', 'b', 'c']);
+        assert_eq!(parse_person("abC"), Err(ParseError::UnexpectedChar('C')));
+        let groups = parse_input("ab\r\nb\r\n\r\nc\r\n").unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(sum_query(&groups, Query::Intersection), 2);
+        assert_eq!(parse_input("a\n\nb1"), Err(ParseError::UnexpectedChar('1')));
     }
 
     #[test]
@@ -64,39 +325,72 @@ mod tests {
 abcy
 abcz";
         let expected = vec!['a', 'b', 'c', 'x', 'y', 'z'];
-        let actual = parse_group(group);
-        assert_eq!(actual, expected);
+        let actual = parse_group(group).unwrap().query(Query::Union);
+        assert_eq!(actual.questions(), expected);
     }
 
     #[test]
     fn test_parse_input() {
-        let input = "abc
-
-a
-b
-c
-
-ab
-ac
-
-a
-a
-a
-a
-
-b";
-        let groups = parse_input(input, parse_group);
+        let groups = parse_input(INPUT).unwrap();
 
         assert_eq!(groups.len(), 5);
-        assert_eq!(groups[2], vec!['a', 'b', 'c']);
+        assert_eq!(groups[2].query(Query::Union).questions(), vec!['a', 'b', 'c']);
     }
 
     #[test]
-    fn test_parse_group_all() {
+    fn test_query_intersection() {
         let group = "ab
 ac";
         let expected = vec!['a'];
-        let actual = parse_group_all(group);
-        assert_eq!(actual, expected);
+        let actual = parse_group(group).unwrap().query(Query::Intersection);
+        assert_eq!(actual.questions(), expected);
+    }
+
+    #[test]
+    fn test_query_counts() {
+        let group = parse_group("abc
+abd
+ae").unwrap();
+        assert_eq!(group.query(Query::AtLeast(2)).questions(), vec!['a', 'b']);
+        assert_eq!(group.query(Query::ExactlyOne).questions(), vec!['c', 'd', 'e']);
+        assert_eq!(group.query(Query::SymmetricDifference).questions(), vec!['a', 'c', 'd', 'e']);
+    }
+
+    #[test]
+    fn test_sum_query() {
+        let groups = parse_input(INPUT).unwrap();
+        assert_eq!(sum_query(&groups, Query::Union), 11);
+        assert_eq!(sum_query(&groups, Query::Intersection), 6);
+    }
+
+    #[test]
+    fn test_question_stats() {
+        let groups = parse_input(INPUT).unwrap();
+        let stats = question_stats(&groups);
+        assert_eq!(stats.len(), 26);
+        assert_eq!(stats[0], QuestionStats {question: 'a', people: 8, groups: 4, unanimous: 3});
+        assert_eq!(stats[25], QuestionStats {question: 'z', people: 0, groups: 0, unanimous: 0});
+    }
+
+    #[test]
+    fn test_report_from_groups() {
+        let groups = parse_input(INPUT).unwrap();
+        let report = Report::from_groups(&groups);
+        assert_eq!(report.groups, 5);
+        assert_eq!(report.people, 11);
+        assert_eq!(report.group_sizes.into_iter().collect::<Vec<_>>(), vec![(1, 2), (2, 1), (3, 1), (4, 1)]);
+        assert_eq!(report.largest_group, Some((3, 4)));
+        assert_eq!(report.smallest_group, Some((0, 1)));
+        assert!((report.unanimous_rate - 6.0 / 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_report_to_json() {
+        let groups = parse_input("ab\nb").unwrap();
+        let json = Report::from_groups(&groups).to_json();
+        assert!(json.starts_with("{\"groups\":1,\"people\":2,\"unanimous_rate\":0.5,"));
+        assert!(json.contains("\"largest_group\":{\"index\":0,\"size\":2}"));
+        assert!(json.contains("\"group_sizes\":{\"2\":1}"));
+        assert!(json.contains("{\"question\":\"b\",\"people\":2,\"groups\":1,\"unanimous\":1}"));
     }
 }
@@ -1,48 +1,73 @@
-use std::collections::HashMap;
-use std::io::{self, Read};
+use std::cell::OnceCell;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::process;
 use lazy_static::lazy_static;
+use num_bigint::BigUint;
 use regex::Regex;
 
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    BadRule(String),
+    BadCount(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ParseError::BadRule(rule) => write!(f, "Can't parse rule: {}", rule),
+            ParseError::BadCount(count) => write!(f, "Bag count doesn't fit in a u64: {}", count),
+        };
+    }
+}
+
 struct Rule<'a> {
     colour: &'a str,
-    contains: Vec<(&'a str, u8)>,
+    contains: Vec<(&'a str, u64)>,
 }
 
 impl<'a> Rule<'a> {
-    fn from_string(rule_str: &'a str) -> Self {
+    fn from_string(rule_str: &'a str) -> Result<Self, ParseError> {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"([a-zA-Z ]+) bags contain (.+).").unwrap();
         }
 
-        let captures = RE.captures(rule_str).unwrap();
+        let captures = RE.captures(rule_str)
+            .ok_or_else(|| ParseError::BadRule(rule_str.to_owned()))?;
         let colour = captures.get(1).unwrap().as_str();
         let sub_bags_str = captures.get(2).unwrap().as_str();
-        let sub_bags = Self::parse_sub_bags(sub_bags_str);
+        let sub_bags = Self::parse_sub_bags(sub_bags_str)?;
 
-        return Rule {
+        return Ok(Rule {
             colour,
             contains: sub_bags,
-        };
+        });
     }
 
-    fn parse_sub_bags(sub_bags_str: &str) -> Vec<(&str, u8)> {
+    fn parse_sub_bags(sub_bags_str: &str) -> Result<Vec<(&str, u64)>, ParseError> {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"\s*(\d+) ([a-zA-Z ]+) bag").unwrap();
         }
 
         let mut bags = Vec::new();
         if sub_bags_str == "no other bags" {
-            return bags;
+            return Ok(bags);
         }
 
         for bag_str in sub_bags_str.split(',') {
-            let captures = RE.captures(bag_str).unwrap();
-            let num: u8 = captures.get(1).unwrap().as_str().parse().unwrap();
+            let captures = RE.captures(bag_str)
+                .ok_or_else(|| ParseError::BadRule(sub_bags_str.to_owned()))?;
+            // The regex only lets digits through, so this can only fail by overflowing
+            let num_str = captures.get(1).unwrap().as_str();
+            let num: u64 = num_str.parse().map_err(|_| ParseError::BadCount(num_str.to_owned()))?;
             let colour = captures.get(2).unwrap().as_str();
             bags.push((colour, num));
         }
 
-        return bags;
+        return Ok(bags);
     }
 
     fn can_contain(&self, colour: &str) -> bool {
@@ -50,17 +75,321 @@ impl<'a> Rule<'a> {
     }
 }
 
-fn count_bags(colour: &str, rules: &HashMap<&str, Rule>) -> usize {
-    let mut total = 0;
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum GraphError {
+    Cycle(Vec<String>),
+    UnknownColour(String),
+    Overflow(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            GraphError::Cycle(path) => write!(f, "Cycle detected: {}", path.join(" -> ")),
+            GraphError::UnknownColour(colour) => write!(f, "Unknown colour: {}", colour),
+            GraphError::Overflow(colour) => write!(f, "Bag total overflows for: {}", colour),
+        };
+    }
+}
+
+struct BagGraph<'a> {
+    colours: Vec<&'a str>,
+    index: HashMap<&'a str, usize>,
+    contains: Vec<Vec<(usize, u64)>>,
+    contained_by: Vec<Vec<usize>>,
+    /// Every bag after the bags it contains, or the cycle that means there's
+    /// no such order. Only totals need it, so a cycle isn't an error until then.
+    order: Result<Vec<usize>, GraphError>,
+    counts: Vec<Option<u64>>,
+    /// Worked out the first time a total doesn't fit in `counts`
+    big_counts: OnceCell<Vec<BigUint>>,
+}
+
+impl<'a> BagGraph<'a> {
+    fn from_rules(rules: &HashMap<&'a str, Rule<'a>>) -> Self {
+        let mut colours: Vec<&str> = rules.keys().copied().collect();
+        colours.sort_unstable();
+        let mut index: HashMap<&str, usize> = colours.iter().enumerate()
+            .map(|(i, c)| (*c, i))
+            .collect();
+
+        // Colours that are only ever contained still need a node
+        for rule in rules.values() {
+            for (colour, _count) in &rule.contains {
+                if !index.contains_key(colour) {
+                    index.insert(colour, colours.len());
+                    colours.push(colour);
+                }
+            }
+        }
+
+        let mut contains = vec![Vec::new(); colours.len()];
+        let mut contained_by = vec![Vec::new(); colours.len()];
+        for rule in rules.values() {
+            let parent = index[rule.colour];
+            for (colour, count) in &rule.contains {
+                let child = index[colour];
+                contains[parent].push((child, *count));
+                contained_by[child].push(parent);
+            }
+        }
+
+        let mut graph = BagGraph {
+            colours,
+            index,
+            contains,
+            contained_by,
+            order: Ok(Vec::new()),
+            counts: Vec::new(),
+            big_counts: OnceCell::new(),
+        };
+        graph.order = graph.topological_order();
+        if let Ok(order) = &graph.order {
+            graph.counts = graph.count_all(order);
+        }
+        return graph;
+    }
+
+    /// Orders the nodes so that every bag comes after all the bags it contains
+    fn topological_order(&self) -> Result<Vec<usize>, GraphError> {
+        const UNVISITED: u8 = 0;
+        const VISITING: u8 = 1;
+        const VISITED: u8 = 2;
+
+        let mut state = vec![UNVISITED; self.colours.len()];
+        let mut order = Vec::with_capacity(self.colours.len());
+
+        for root in 0..self.colours.len() {
+            if state[root] != UNVISITED {
+                continue;
+            }
+            let mut stack = vec![(root, 0)];
+            state[root] = VISITING;
+
+            while let Some((node, next)) = stack.pop() {
+                match self.contains[node].get(next) {
+                    None => {
+                        state[node] = VISITED;
+                        order.push(node);
+                    },
+                    Some(&(child, _count)) => {
+                        stack.push((node, next + 1));
+                        match state[child] {
+                            UNVISITED => {
+                                state[child] = VISITING;
+                                stack.push((child, 0));
+                            },
+                            VISITING => {
+                                let start = stack.iter().position(|(n, _)| *n == child).unwrap();
+                                let mut cycle: Vec<String> = stack[start..].iter()
+                                    .map(|(n, _)| self.colours[*n].to_owned())
+                                    .collect();
+                                cycle.push(self.colours[child].to_owned());
+                                return Err(GraphError::Cycle(cycle));
+                            },
+                            _ => (),
+                        }
+                    },
+                }
+            }
+        }
+
+        return Ok(order);
+    }
+
+    /// Totals for every bag, or `None` where the total doesn't fit in a `u64`
+    fn count_all(&self, order: &[usize]) -> Vec<Option<u64>> {
+        let mut counts: Vec<Option<u64>> = vec![None; self.colours.len()];
+        for &node in order {
+            counts[node] = self.contains[node].iter().try_fold(0u64, |total, &(child, count)| {
+                let inner = counts[child]?.checked_add(1)?;
+                total.checked_add(count.checked_mul(inner)?)
+            });
+        }
+        return counts;
+    }
+
+    fn count_all_big(&self, order: &[usize]) -> Vec<BigUint> {
+        let mut counts = vec![BigUint::default(); self.colours.len()];
+        for &node in order {
+            counts[node] = self.contains[node].iter().map(|&(child, count)| {
+                BigUint::from(count) * (&counts[child] + 1u32)
+            }).sum();
+        }
+        return counts;
+    }
+
+    fn node(&self, colour: &str) -> Result<usize, GraphError> {
+        return self.index.get(colour).copied()
+            .ok_or_else(|| GraphError::UnknownColour(colour.to_owned()));
+    }
+
+    fn count_bags(&self, colour: &str) -> Result<u64, GraphError> {
+        let node = self.node(colour)?;
+        self.order.as_ref().map_err(|e| e.clone())?;
+        return self.counts[node].ok_or_else(|| GraphError::Overflow(colour.to_owned()));
+    }
+
+    fn count_bags_big(&self, colour: &str) -> Result<BigUint, GraphError> {
+        let node = self.node(colour)?;
+        let order = self.order.as_ref().map_err(|e| e.clone())?;
+        return Ok(self.big_counts.get_or_init(|| self.count_all_big(order))[node].clone());
+    }
+
+    fn ancestors(&self, colour: &str) -> Result<Vec<&'a str>, GraphError> {
+        return Ok(self.marked_colours(&self.reachable(self.node(colour)?, true)));
+    }
+
+    fn descendants(&self, colour: &str) -> Result<Vec<&'a str>, GraphError> {
+        return Ok(self.marked_colours(&self.reachable(self.node(colour)?, false)));
+    }
+
+    /// Marks every bag one or more steps from `start`, going down into the
+    /// bags it contains or, `upwards`, out to the bags containing it
+    fn reachable(&self, start: usize, upwards: bool) -> Vec<bool> {
+        let push_next = |queue: &mut VecDeque<usize>, node: usize| {
+            if upwards {
+                queue.extend(&self.contained_by[node]);
+            } else {
+                queue.extend(self.contains[node].iter().map(|&(child, _count)| child));
+            }
+        };
+        let mut seen = vec![false; self.colours.len()];
+        let mut queue = VecDeque::new();
+        push_next(&mut queue, start);
+
+        while let Some(node) = queue.pop_front() {
+            if seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            push_next(&mut queue, node);
+        }
+
+        return seen;
+    }
+
+    /// Same as `reachable`, but starting from a colour and marking it too
+    fn reachable_from(&self, colour: &str, upwards: bool) -> Result<Vec<bool>, GraphError> {
+        let start = self.node(colour)?;
+        let mut marked = self.reachable(start, upwards);
+        marked[start] = true;
+        return Ok(marked);
+    }
+
+    fn marked_colours(&self, marked: &[bool]) -> Vec<&'a str> {
+        let mut colours: Vec<_> = self.colours.iter().zip(marked)
+            .filter(|(_, &marked)| marked)
+            .map(|(colour, _)| *colour)
+            .collect();
+        colours.sort_unstable();
+        return colours;
+    }
+
+    /// Finds the shortest chain of bags from `outer` down to `inner`, inclusive
+    fn shortest_chain(&self, outer: &str, inner: &str) -> Result<Option<Vec<&'a str>>, GraphError> {
+        let start = self.node(outer)?;
+        let end = self.node(inner)?;
+        let mut parents = vec![None; self.colours.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+
+        while let Some(node) = queue.pop_front() {
+            if node == end {
+                let mut chain = vec![self.colours[end]];
+                let mut current = end;
+                while let Some(parent) = parents[current] {
+                    chain.push(self.colours[parent]);
+                    current = parent;
+                }
+                chain.reverse();
+                return Ok(Some(chain));
+            }
+            for &(child, _count) in &self.contains[node] {
+                if child != start && parents[child].is_none() {
+                    parents[child] = Some(node);
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        return Ok(None);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum BagQuery {
+    Containers(String),
+    Contents(String),
+    AllContents(String),
+    Count(String),
+    Chain(String, String),
+    Paths(String),
+}
 
-    let rule = &rules[colour];
-    for (sub_colour, sub_count) in &rule.contains {
-        let count = *sub_count as usize;
-        total += count;
-        total += count * count_bags(sub_colour, rules);
+impl BagQuery {
+    fn from_str(query_str: &str) -> Result<Self, String> {
+        let query_str = query_str.trim();
+        let (command, colour) = query_str.split_once(' ')
+            .ok_or_else(|| format!("Missing colour in query: {}", query_str))?;
+        let colour = colour.trim().to_owned();
+
+        return match command {
+            "containers" => Ok(Self::Containers(colour)),
+            "contents" => Ok(Self::Contents(colour)),
+            "all-contents" => Ok(Self::AllContents(colour)),
+            "count" => Ok(Self::Count(colour)),
+            "paths" => Ok(Self::Paths(colour)),
+            "chain" => match colour.split_once(',') {
+                Some((outer, inner)) => Ok(Self::Chain(outer.trim().to_owned(), inner.trim().to_owned())),
+                None => Err(format!("Expected 'chain <outer>, <inner>': {}", query_str)),
+            },
+            _ => Err(format!("Unknown query: {}", query_str)),
+        };
+    }
+
+    fn answer(&self, graph: &BagGraph, rules: &HashMap<&str, Rule>) -> Result<String, GraphError> {
+        return match self {
+            Self::Containers(colour) => Ok(graph.ancestors(colour)?.join(", ")),
+            Self::Contents(colour) => {
+                graph.node(colour)?;
+                let contents: Vec<_> = rules.get(colour.as_str())
+                    .map(|r| r.contains.iter().map(|(c, n)| format!("{} {}", n, c)).collect())
+                    .unwrap_or_default();
+                Ok(contents.join(", "))
+            },
+            Self::AllContents(colour) => Ok(graph.descendants(colour)?.join(", ")),
+            Self::Count(colour) => match graph.count_bags(colour) {
+                Err(GraphError::Overflow(_)) => Ok(graph.count_bags_big(colour)?.to_string()),
+                count => Ok(count?.to_string()),
+            },
+            Self::Chain(outer, inner) => Ok(match graph.shortest_chain(outer, inner)? {
+                Some(chain) => chain.join(" -> "),
+                None => String::from("no chain"),
+            }),
+            Self::Paths(colour) => {
+                graph.node(colour)?;
+                // Every path is listed, which never ends if the rules loop
+                graph.order.as_ref().map_err(|e| e.clone())?;
+                let mut paths: Vec<String> = find_paths(colour, rules).iter()
+                    .map(|path| path.join(", "))
+                    .collect();
+                paths.sort_unstable();
+                Ok(paths.join("; "))
+            },
+        };
     }
+}
 
-    return total;
+fn run_queries<I: Iterator<Item = String>>(queries: I, graph: &BagGraph, rules: &HashMap<&str, Rule>) {
+    for query_str in queries.filter(|q| !q.trim().is_empty()) {
+        let result = BagQuery::from_str(&query_str)
+            .and_then(|q| q.answer(graph, rules).map_err(|e| e.to_string()));
+        match result {
+            Ok(answer) => println!("{}: {}", query_str.trim(), answer),
+            Err(e) => println!("{}: error: {}", query_str.trim(), e),
+        }
+    }
 }
 
 fn find_paths<'a>(colour: &'a str, rules: &'a HashMap<&str, Rule>) -> Vec<Vec<&'a str>> {
@@ -85,44 +414,201 @@ fn find_paths<'a>(colour: &'a str, rules: &'a HashMap<&str, Rule>) -> Vec<Vec<&'
     return all_paths;
 }
 
-fn parse_rules(input: &str) -> HashMap<&str, Rule> {
+fn parse_rules(input: &str) -> Result<HashMap<&str, Rule>, ParseError> {
     let mut rules = HashMap::new();
 
     for line in input.lines() {
-        let rule = Rule::from_string(line);
+        let rule = Rule::from_string(line)?;
         rules.insert(rule.colour, rule);
     }
 
-    return rules;
+    return Ok(rules);
+}
+
+#[derive(Default)]
+struct DotOptions<'a> {
+    from: Option<&'a str>,
+    to: Option<&'a str>,
+    /// A chain of bags, outermost first, to pick out along with the rules
+    /// between them
+    highlight: Option<&'a [&'a str]>,
+}
+
+fn to_dot(graph: &BagGraph, options: &DotOptions) -> Result<String, GraphError> {
+    let below_from = options.from.map(|c| graph.reachable_from(c, false)).transpose()?;
+    let above_to = options.to.map(|c| graph.reachable_from(c, true)).transpose()?;
+    let included = |node: usize| {
+        below_from.as_ref().is_none_or(|f| f[node]) && above_to.as_ref().is_none_or(|t| t[node])
+    };
+    let path = options.highlight.unwrap_or(&[]);
+    let on_path = |outer: &str, inner: &str| {
+        path.windows(2).any(|w| w[0] == outer && w[1] == inner)
+    };
+
+    let mut nodes: Vec<usize> = (0..graph.colours.len()).filter(|&n| included(n)).collect();
+    nodes.sort_unstable_by_key(|&n| graph.colours[n]);
+
+    let mut dot = String::from("digraph bags {\n");
+    for &node in &nodes {
+        if path.contains(&graph.colours[node]) {
+            dot.push_str(&format!("    \"{}\" [color=red, penwidth=2];\n", graph.colours[node]));
+        } else {
+            dot.push_str(&format!("    \"{}\";\n", graph.colours[node]));
+        }
+    }
+    for &node in &nodes {
+        for &(child, count) in graph.contains[node].iter().filter(|(c, _n)| included(*c)) {
+            let highlighted = on_path(graph.colours[node], graph.colours[child]);
+            let style = if highlighted { ", color=red, penwidth=2" } else { "" };
+            dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                graph.colours[node], graph.colours[child], count, style));
+        }
+    }
+    dot.push_str("}\n");
+
+    return Ok(dot);
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("--{}=", name);
+    return args.iter().find_map(|a| a.strip_prefix(&prefix));
+}
+
+/// The chain of bags to highlight: a path as listed by the `paths` query,
+/// like `light red, bright white, shiny gold`, or a colour to follow down to
+/// the `--to` colour by the shortest chain
+fn highlight_path<'a>(graph: &BagGraph<'a>, rules: &HashMap<&str, Rule>, highlight: &'a str,
+        to: Option<&str>) -> Result<Vec<&'a str>, String> {
+    if highlight.contains(',') {
+        let path: Vec<&str> = highlight.split(',').map(|c| c.trim()).collect();
+        for pair in path.windows(2) {
+            if !rules.get(pair[0]).is_some_and(|r| r.can_contain(pair[1])) {
+                return Err(format!("{} bags can't contain {} bags", pair[0], pair[1]));
+            }
+        }
+        return Ok(path);
+    }
+    let to = to.ok_or("--highlight needs --to for where the path ends, or a whole path")?;
+    return match graph.shortest_chain(highlight, to) {
+        Ok(Some(chain)) => Ok(chain),
+        Ok(None) => Err(format!("No chain from {} down to {}", highlight, to)),
+        Err(e) => Err(e.to_string()),
+    };
+}
+
+fn print_dot(graph: &BagGraph, rules: &HashMap<&str, Rule>, args: &[String]) {
+    let to = arg_value(args, "to");
+    let path = match arg_value(args, "highlight") {
+        Some(highlight) => match highlight_path(graph, rules, highlight, to) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            },
+        },
+        None => None,
+    };
+    let options = DotOptions {
+        from: arg_value(args, "from"),
+        to,
+        highlight: path.as_deref(),
+    };
+    match to_dot(graph, &options) {
+        Ok(dot) => print!("{}", dot),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    }
 }
 
-fn part1(rules: &HashMap<&str, Rule>) {
-    let mut colours: Vec<&str> = find_paths("shiny gold", rules).iter()
-        .map(|p| *p.first().unwrap()).collect();
-    colours.sort_unstable();
-    colours.dedup();
+fn part1(graph: &BagGraph) {
+    let colours = graph.ancestors("shiny gold").unwrap();
     println!("Colours that can eventually contain shiny gold: {}",
         colours.len());
 }
 
-fn part2(rules: &HashMap<&str, Rule>) {
-    let bags = count_bags("shiny gold", rules);
-    println!("Bags required: {}", bags);
+fn part2(graph: &BagGraph, big: bool) {
+    let bags = if big {
+        graph.count_bags_big("shiny gold").map(|n| n.to_string())
+    } else {
+        graph.count_bags("shiny gold").map(|n| n.to_string())
+    };
+    match bags {
+        Ok(bags) => println!("Bags required: {}", bags),
+        Err(e @ GraphError::Overflow(_)) => {
+            eprintln!("{} (try --big)", e);
+            process::exit(1);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    }
+    let colours = graph.descendants("shiny gold").unwrap();
+    println!("Distinct colours inside shiny gold: {}", colours.len());
 }
 
 fn main() {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input).unwrap();
-    let rules = parse_rules(&input);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rules_file = arg_value(&args, "rules");
+    let input = match rules_file {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Can't read {}: {}", path, e);
+                process::exit(1);
+            },
+        },
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).unwrap();
+            input
+        },
+    };
+    let rules = match parse_rules(&input) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    };
+    let graph = BagGraph::from_rules(&rules);
+
+    if args.iter().any(|a| a == "--dot") {
+        print_dot(&graph, &rules, &args);
+        return;
+    }
 
-    part1(&rules);
-    part2(&rules);
+    // With the rules in a file, queries come from the arguments or stdin
+    if rules_file.is_some() {
+        let queries: Vec<_> = args.iter().filter(|a| !a.starts_with("--")).cloned().collect();
+        if queries.is_empty() {
+            run_queries(io::stdin().lock().lines().map(|l| l.unwrap()), &graph, &rules);
+        } else {
+            run_queries(queries.into_iter(), &graph, &rules);
+        }
+        return;
+    }
+
+    part1(&graph);
+    part2(&graph, args.iter().any(|a| a == "--big"));
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const INPUT: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.";
+
     fn create_rule() -> Rule<'static> {
         return Rule {
             colour: "light red",
@@ -136,7 +622,7 @@ mod tests {
     #[test]
     fn test_rule_parse_sub_bags_1() {
         let sub_bags_str = "1 bright white bag, 2 muted yellow bags";
-        let sub_bags = Rule::parse_sub_bags(sub_bags_str);
+        let sub_bags = Rule::parse_sub_bags(sub_bags_str).unwrap();
         assert_eq!(sub_bags.len(), 2);
         assert_eq!(sub_bags[0], ("bright white", 1));
         assert_eq!(sub_bags[1], ("muted yellow", 2));
@@ -145,14 +631,23 @@ mod tests {
     #[test]
     fn test_rule_parse_sub_bags_2() {
         let sub_bags_str = "no other bags";
-        let sub_bags = Rule::parse_sub_bags(sub_bags_str);
+        let sub_bags = Rule::parse_sub_bags(sub_bags_str).unwrap();
         assert!(sub_bags.is_empty());
     }
 
+    #[test]
+    fn test_rule_parse_errors() {
+        assert_eq!(Rule::parse_sub_bags("18446744073709551616 dark red bags").err(),
+            Some(ParseError::BadCount(String::from("18446744073709551616"))));
+        assert_eq!(Rule::from_string("shiny gold bags").err(),
+            Some(ParseError::BadRule(String::from("shiny gold bags"))));
+        assert!(parse_rules("shiny gold bags contain some bags.").is_err());
+    }
+
     #[test]
     fn test_rule_from_string_1() {
         let rule_str = "light red bags contain 1 bright white bag, 2 muted yellow bags.";
-        let rule = Rule::from_string(rule_str);
+        let rule = Rule::from_string(rule_str).unwrap();
         assert_eq!(rule.colour, "light red");
         assert_eq!(rule.contains.len(), 2);
         assert_eq!(rule.contains[0], ("bright white", 1));
@@ -162,7 +657,7 @@ mod tests {
     #[test]
     fn test_rule_from_string_2() {
         let rule_str = "faded blue bags contain no other bags.";
-        let rule = Rule::from_string(rule_str);
+        let rule = Rule::from_string(rule_str).unwrap();
         assert_eq!(rule.colour, "faded blue");
         assert!(rule.contains.is_empty());
     }
@@ -178,7 +673,7 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
-        let rules = parse_rules(input);
+        let rules = parse_rules(input).unwrap();
         assert_eq!(rules.len(), 9);
         assert_eq!(rules["light red"].colour, "light red");
         assert_eq!(rules["light red"].contains.len(), 2);
@@ -207,7 +702,7 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
-        let rules = parse_rules(input);
+        let rules = parse_rules(input).unwrap();
         let colour = "shiny gold";
         let paths = find_paths(colour, &rules);
         let expected: Vec<Vec<&str>> = vec![
@@ -225,18 +720,188 @@ dotted black bags contain no other bags.";
 
     #[test]
     fn test_count_bags() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(graph.count_bags("shiny gold"), Ok(32));
+        assert_eq!(graph.count_bags("faded blue"), Ok(0));
+        assert_eq!(graph.count_bags("lime green"),
+            Err(GraphError::UnknownColour(String::from("lime green"))));
+    }
+
+    #[test]
+    fn test_count_bags_deep() {
+        let input = "shiny gold bags contain 2 dark red bags.
+dark red bags contain 2 dark orange bags.
+dark orange bags contain 2 dark yellow bags.
+dark yellow bags contain 2 dark green bags.
+dark green bags contain 2 dark blue bags.
+dark blue bags contain 2 dark violet bags.
+dark violet bags contain no other bags.";
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(graph.count_bags("shiny gold"), Ok(126));
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(graph.ancestors("shiny gold"),
+            Ok(vec!["bright white", "dark orange", "light red", "muted yellow"]));
+        assert_eq!(graph.descendants("shiny gold"),
+            Ok(vec!["dark olive", "dotted black", "faded blue", "vibrant plum"]));
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let input = "shiny gold bags contain 1 dark red bag.
+dark red bags contain 2 faded blue bags.
+faded blue bags contain 3 shiny gold bags.";
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        match graph.count_bags("dark red") {
+            Err(GraphError::Cycle(path)) => {
+                assert_eq!(path.len(), 4);
+                assert_eq!(path.first(), path.last());
+            },
+            _ => panic!("Expected a cycle"),
+        }
+        assert!(matches!(graph.count_bags_big("shiny gold"), Err(GraphError::Cycle(_))));
+        assert_eq!(graph.count_bags("lime green"),
+            Err(GraphError::UnknownColour(String::from("lime green"))));
+        let answer = |q: &str| BagQuery::from_str(q).unwrap().answer(&graph, &rules);
+        assert!(matches!(answer("paths dark red"), Err(GraphError::Cycle(_))));
+        // Only totals and listing every path care about the cycle
+        assert_eq!(graph.ancestors("shiny gold"),
+            Ok(vec!["dark red", "faded blue", "shiny gold"]));
+        assert_eq!(graph.descendants("dark red"),
+            Ok(vec!["dark red", "faded blue", "shiny gold"]));
+        assert_eq!(graph.shortest_chain("dark red", "shiny gold"),
+            Ok(Some(vec!["dark red", "faded blue", "shiny gold"])));
+    }
+
+    #[test]
+    fn test_to_dot() {
         let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.
-dark orange bags contain 3 bright white bags, 4 muted yellow bags.
 bright white bags contain 1 shiny gold bag.
-muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
-shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
-dark olive bags contain 3 faded blue bags, 4 dotted black bags.
-vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
-faded blue bags contain no other bags.
-dotted black bags contain no other bags.";
-        let rules = parse_rules(input);
-        let colour = "shiny gold";
-        let count = count_bags(colour, &rules);
-        assert_eq!(count, 32);
+muted yellow bags contain no other bags.";
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        let dot = to_dot(&graph, &DotOptions::default()).unwrap();
+        assert_eq!(dot, "digraph bags {
+    \"bright white\";
+    \"light red\";
+    \"muted yellow\";
+    \"shiny gold\";
+    \"bright white\" -> \"shiny gold\" [label=\"1\"];
+    \"light red\" -> \"bright white\" [label=\"1\"];
+    \"light red\" -> \"muted yellow\" [label=\"2\"];
+}
+");
+    }
+
+    #[test]
+    fn test_to_dot_restricted() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        let options = DotOptions {to: Some("shiny gold"), ..Default::default()};
+        let dot = to_dot(&graph, &options).unwrap();
+        assert!(dot.contains("\"dark orange\" -> \"muted yellow\" [label=\"4\"];"));
+        assert!(!dot.contains("faded blue"));
+
+        let options = DotOptions {from: Some("shiny gold"), ..Default::default()};
+        let dot = to_dot(&graph, &options).unwrap();
+        assert!(dot.contains("\"vibrant plum\" -> \"dotted black\" [label=\"6\"];"));
+        assert!(!dot.contains("light red"));
+
+        let options = DotOptions {from: Some("lime green"), ..Default::default()};
+        assert_eq!(to_dot(&graph, &options),
+            Err(GraphError::UnknownColour(String::from("lime green"))));
+    }
+
+    #[test]
+    fn test_to_dot_highlight() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        let path = vec!["light red", "bright white", "shiny gold"];
+        let options = DotOptions {highlight: Some(&path), ..Default::default()};
+        let dot = to_dot(&graph, &options).unwrap();
+        assert!(dot.contains("\"light red\" [color=red, penwidth=2];"));
+        assert!(dot.contains("\"muted yellow\";"));
+        assert!(dot.contains("\"bright white\" -> \"shiny gold\" [label=\"1\", color=red, penwidth=2];"));
+        assert!(dot.contains("\"light red\" -> \"muted yellow\" [label=\"2\"];"));
+        assert!(dot.contains("\"dark orange\" -> \"bright white\" [label=\"3\"];"));
+    }
+
+    #[test]
+    fn test_highlight_path() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(highlight_path(&graph, &rules, "dark orange, muted yellow, shiny gold", None),
+            Ok(vec!["dark orange", "muted yellow", "shiny gold"]));
+        assert!(highlight_path(&graph, &rules, "dark orange, shiny gold", None).is_err());
+        assert_eq!(highlight_path(&graph, &rules, "light red", Some("faded blue")),
+            Ok(vec!["light red", "muted yellow", "faded blue"]));
+        assert!(highlight_path(&graph, &rules, "light red", None).is_err());
+        assert!(highlight_path(&graph, &rules, "faded blue", Some("light red")).is_err());
+        assert!(highlight_path(&graph, &rules, "lime green", Some("light red")).is_err());
+    }
+
+    #[test]
+    fn test_count_bags_overflow() {
+        let input = "shiny gold bags contain 100000 dark red bags.
+dark red bags contain 100000 dark orange bags.
+dark orange bags contain 100000 dark yellow bags.
+dark yellow bags contain 100000 dark green bags.
+dark green bags contain no other bags.";
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(graph.count_bags("dark orange"), Ok(10_000_100_000));
+        assert_eq!(graph.count_bags("shiny gold"),
+            Err(GraphError::Overflow(String::from("shiny gold"))));
+        assert_eq!(graph.count_bags_big("shiny gold").unwrap().to_string(),
+            "100001000010000100000");
+        assert_eq!(graph.count_bags_big("dark orange"), Ok(BigUint::from(10_000_100_000u64)));
+        let answer = |q: &str| BagQuery::from_str(q).unwrap().answer(&graph, &rules);
+        assert_eq!(answer("count shiny gold"), Ok(String::from("100001000010000100000")));
+        assert_eq!(answer("count dark orange"), Ok(String::from("10000100000")));
+    }
+
+    #[test]
+    fn test_shortest_chain() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        assert_eq!(graph.shortest_chain("light red", "faded blue"),
+            Ok(Some(vec!["light red", "muted yellow", "faded blue"])));
+        assert_eq!(graph.shortest_chain("shiny gold", "shiny gold"), Ok(Some(vec!["shiny gold"])));
+        assert_eq!(graph.shortest_chain("faded blue", "shiny gold"), Ok(None));
+    }
+
+    #[test]
+    fn test_bag_query_from_str() {
+        assert_eq!(BagQuery::from_str("count shiny gold"),
+            Ok(BagQuery::Count(String::from("shiny gold"))));
+        assert_eq!(BagQuery::from_str("chain light red, shiny gold"),
+            Ok(BagQuery::Chain(String::from("light red"), String::from("shiny gold"))));
+        assert!(BagQuery::from_str("chain light red").is_err());
+        assert!(BagQuery::from_str("paint shiny gold").is_err());
+    }
+
+    #[test]
+    fn test_bag_query_answer() {
+        let rules = parse_rules(INPUT).unwrap();
+        let graph = BagGraph::from_rules(&rules);
+        let answer = |q: &str| BagQuery::from_str(q).unwrap().answer(&graph, &rules);
+        assert_eq!(answer("containers bright white"), Ok(String::from("dark orange, light red")));
+        assert_eq!(answer("contents shiny gold"), Ok(String::from("1 dark olive, 2 vibrant plum")));
+        assert_eq!(answer("contents faded blue"), Ok(String::new()));
+        assert_eq!(answer("all-contents dark olive"), Ok(String::from("dotted black, faded blue")));
+        assert_eq!(answer("count shiny gold"), Ok(String::from("32")));
+        assert_eq!(answer("chain dark orange, dotted black"),
+            Ok(String::from("dark orange -> bright white -> shiny gold -> dark olive -> dotted black")));
+        assert_eq!(answer("count lime green"),
+            Err(GraphError::UnknownColour(String::from("lime green"))));
+        assert_eq!(answer("paths bright white"),
+            Ok(String::from("dark orange, bright white; light red, bright white")));
     }
 }
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::{Instruction, Opcode};
+
+/// Marks the start of an encoded program
+pub const MAGIC: &[u8] = b"BOOT";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AsmError {
+    BadInstruction(usize, String),
+    DuplicateLabel(usize, String),
+    UnknownLabel(usize, String),
+    BadMagic,
+    Truncated,
+    /// A varint with more bits than fit in a `usize`
+    Overflow,
+    /// Bytes left over after the last instruction
+    TrailingBytes(usize),
+    UnknownOpcode(u8),
+    BadRegister(u8),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            AsmError::BadInstruction(line, text) => write!(f, "line {}: bad instruction: {}", line, text),
+            AsmError::DuplicateLabel(line, label) => write!(f, "line {}: duplicate label: {}", line, label),
+            AsmError::UnknownLabel(line, label) => write!(f, "line {}: unknown label: {}", line, label),
+            AsmError::BadMagic => write!(f, "not an encoded program"),
+            AsmError::Truncated => write!(f, "encoded program is truncated"),
+            AsmError::Overflow => write!(f, "encoded number is too large"),
+            AsmError::TrailingBytes(count) => write!(f, "{} bytes after the end of the program", count),
+            AsmError::UnknownOpcode(code) => write!(f, "unknown opcode: {}", code),
+            AsmError::BadRegister(register) => write!(f, "bad register: {}", register),
+        };
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    return chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+}
+
+/// Splits a source line into its labels and the instruction text after them
+fn split_labels(line: &str) -> (Vec<&str>, &str) {
+    let mut labels = Vec::new();
+    let mut rest = line.split('#').next().unwrap().trim();
+    while let Some((label, after)) = rest.split_once(':') {
+        if !is_label(label.trim()) {
+            break;
+        }
+        labels.push(label.trim());
+        rest = after.trim();
+    }
+    return (labels, rest);
+}
+
+/// Parses source text into a program. On top of the plain `acc +1` form,
+/// lines may start with `name:` labels, jump operands may name a label
+/// instead of giving an offset, and `#` starts a comment.
+pub fn parse_source(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    // First pass to find where every label points
+    let mut labels = HashMap::new();
+    let mut pc = 0;
+    for (i, line) in source.lines().enumerate() {
+        let (line_labels, instr_str) = split_labels(line);
+        for label in line_labels {
+            if labels.insert(label, pc).is_some() {
+                return Err(AsmError::DuplicateLabel(i + 1, label.to_owned()));
+            }
+        }
+        if !instr_str.is_empty() {
+            pc += 1;
+        }
+    }
+
+    let mut program = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let (_, instr_str) = split_labels(line);
+        let parts: Vec<_> = instr_str.split_whitespace().collect();
+        let opcode = match parts.first() {
+            Some(mnemonic) => Opcode::from_mnemonic(mnemonic)
+                .ok_or_else(|| AsmError::BadInstruction(i + 1, instr_str.to_owned()))?,
+            None => continue,
+        };
+
+        // Only the value operand can be a label, so a missing one after a
+        // register isn't mistaken for a jump to a label called `r1`
+        let operands = &parts[1..];
+        let value_index = opcode.has_register as usize;
+        if operands.len() == value_index + 1 {
+            let target = operands[value_index];
+            if is_label(target) && !labels.contains_key(target) {
+                return Err(AsmError::UnknownLabel(i + 1, target.to_owned()));
+            }
+        }
+        let pc = program.len() as isize;
+        let value = |operand: &str| match labels.get(operand) {
+            Some(target) => Some(target - pc),
+            None => operand.parse().ok(),
+        };
+        let instr = opcode.parse(operands, value)
+            .ok_or_else(|| AsmError::BadInstruction(i + 1, instr_str.to_owned()))?;
+        program.push(instr);
+    }
+
+    return Ok(program);
+}
+
+fn push_varint(bytes: &mut Vec<u8>, mut n: usize) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<usize, AsmError> {
+    let mut n: usize = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next().ok_or(AsmError::Truncated)?;
+        let bits = (byte & 0x7f) as usize;
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(AsmError::Overflow);
+        }
+        n |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        shift += 7;
+    }
+}
+
+// Zigzag encode signed values so small negative offsets stay small too
+fn push_signed(bytes: &mut Vec<u8>, value: isize) {
+    push_varint(bytes, ((value << 1) ^ (value >> (isize::BITS - 1))) as usize);
+}
+
+fn read_signed(bytes: &mut impl Iterator<Item = u8>) -> Result<isize, AsmError> {
+    let n = read_varint(bytes)?;
+    return Ok(((n >> 1) as isize) ^ -((n & 1) as isize));
+}
+
+/// Encodes a program as the magic bytes, the instruction count, then each
+/// instruction as its opcode byte, a register byte if it takes one, and a
+/// zigzag varint value
+pub fn encode(program: &[Instruction]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    push_varint(&mut bytes, program.len());
+    for instr in program {
+        let opcode = instr.opcode();
+        let (register, value) = instr.operands();
+        bytes.push(opcode.code);
+        if opcode.has_register {
+            bytes.push(register as u8);
+        }
+        push_signed(&mut bytes, value);
+    }
+    return bytes;
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Instruction>, AsmError> {
+    let body = bytes.strip_prefix(MAGIC).ok_or(AsmError::BadMagic)?;
+    let mut bytes = body.iter().copied();
+    let len = read_varint(&mut bytes)?;
+
+    let mut program = Vec::new();
+    for _ in 0..len {
+        let code = bytes.next().ok_or(AsmError::Truncated)?;
+        let opcode = Opcode::from_code(code).ok_or(AsmError::UnknownOpcode(code))?;
+        let register = match opcode.has_register {
+            true => bytes.next().ok_or(AsmError::Truncated)?,
+            false => 0,
+        };
+        if register as usize >= crate::NUM_REGISTERS {
+            return Err(AsmError::BadRegister(register));
+        }
+        let value = read_signed(&mut bytes)?;
+        program.push((opcode.build)(register as usize, value));
+    }
+
+    let trailing = bytes.count();
+    if trailing > 0 {
+        return Err(AsmError::TrailingBytes(trailing));
+    }
+    return Ok(program);
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    return Ok(encode(&parse_source(source)?));
+}
+
+pub fn disassemble(bytes: &[u8]) -> Result<String, AsmError> {
+    let program = decode(bytes)?;
+    return Ok(program.iter().map(|instr| format!("{}\n", instr)).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use crate::Instruction::*;
+
+    #[test]
+    fn test_parse_source_labels() {
+        let source = "start: acc +1
+    nop +0   # comment
+loop_start:
+    jz r1 done
+    add r1 -1
+    jmp loop_start
+done: jmp start";
+        let program = parse_source(source).unwrap();
+        assert_eq!(program, vec![Acc(1), Nop(0), Jz(1, 3), Add(1, -1), Jmp(-2), Jmp(-5)]);
+    }
+
+    #[test]
+    fn test_parse_source_errors() {
+        assert_eq!(parse_source("jmp nowhere"),
+            Err(AsmError::UnknownLabel(1, String::from("nowhere"))));
+        assert_eq!(parse_source("a: nop +0\na: nop +0"),
+            Err(AsmError::DuplicateLabel(2, String::from("a"))));
+        assert_eq!(parse_source("acc +1\nmul +2"),
+            Err(AsmError::BadInstruction(2, String::from("mul +2"))));
+        assert_eq!(parse_source("add r1"),
+            Err(AsmError::BadInstruction(1, String::from("add r1"))));
+        assert_eq!(parse_source("jz r1 nowhere"),
+            Err(AsmError::UnknownLabel(1, String::from("nowhere"))));
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let program = vec![Acc(1), Jmp(-3), Nop(0), Add(3, 1000), Jz(1, -64), Jnz(0, isize::MIN)];
+        let bytes = encode(&program);
+        assert_eq!(&bytes[..6], b"BOOT\x06\x00");
+        assert_eq!(decode(&bytes), Ok(program));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode(b"BOOX\x00"), Err(AsmError::BadMagic));
+        assert_eq!(decode(b"BOOT\x02\x00\x02\x03"), Err(AsmError::Truncated));
+        assert_eq!(decode(b"BOOT\x01\x09\x02"), Err(AsmError::UnknownOpcode(9)));
+        assert_eq!(decode(b"BOOT\x01\x03\x07\x02"), Err(AsmError::BadRegister(7)));
+        assert_eq!(decode(b"BOOT\x01\x00\x02\x00\x00"), Err(AsmError::TrailingBytes(2)));
+        let mut too_big = b"BOOT\x01\x00".to_vec();
+        too_big.extend([0xff; 9]);
+        too_big.push(0x7f);
+        assert_eq!(decode(&too_big), Err(AsmError::Overflow));
+        let mut too_long = b"BOOT\x01\x00".to_vec();
+        too_long.extend([0x80; 10]);
+        too_long.push(0x01);
+        assert_eq!(decode(&too_long), Err(AsmError::Overflow));
+    }
+
+    #[test]
+    fn test_assemble_round_trip() {
+        let source = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+        let bytes = assemble(source).unwrap();
+        let text = disassemble(&bytes).unwrap();
+        assert_eq!(text, source);
+        assert_eq!(assemble(&text).unwrap(), bytes);
+        assert_eq!(decode(&bytes).unwrap(), parse_program(source));
+    }
+}
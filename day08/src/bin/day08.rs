@@ -0,0 +1,215 @@
+use day08::{run_program, Instruction, Vm};
+use day08::asm::{self, AsmError};
+use day08::debugger::{Breakpoint, Debugger, Stop, TraceEntry};
+use day08::repair::{find_double_repairs, find_repairs};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+
+#[derive(PartialEq, Eq, Debug)]
+enum Command {
+    Step(usize),
+    Continue,
+    Break(Breakpoint),
+    Clear,
+    Print,
+    Trace(usize),
+    Loop,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Option<Command> {
+    let parts: Vec<_> = line.split_whitespace().collect();
+    let number = |i: usize, default| match parts.get(i) {
+        Some(n) => n.parse().ok(),
+        None => Some(default),
+    };
+
+    return match parts.as_slice() {
+        ["s" | "step", ..] => Some(Command::Step(number(1, 1)?)),
+        ["c" | "continue"] => Some(Command::Continue),
+        ["b" | "break", "acc", op, value] => {
+            let value = value.parse().ok()?;
+            match *op {
+                "=" | "==" => Some(Command::Break(Breakpoint::AccEquals(value))),
+                ">" => Some(Command::Break(Breakpoint::AccAbove(value))),
+                "<" => Some(Command::Break(Breakpoint::AccBelow(value))),
+                _ => None,
+            }
+        },
+        ["b" | "break", pc] => Some(Command::Break(Breakpoint::Pc(pc.parse().ok()?))),
+        ["clear"] => Some(Command::Clear),
+        ["p" | "print"] => Some(Command::Print),
+        ["t" | "trace", ..] => Some(Command::Trace(number(1, 10)?)),
+        ["l" | "loop"] => Some(Command::Loop),
+        ["q" | "quit"] => Some(Command::Quit),
+        _ => None,
+    };
+}
+
+fn print_trace_entry(entry: &TraceEntry) {
+    println!("{:>6}  pc {:>4}  {:<12}  acc {}", entry.step, entry.pc, entry.instruction.to_string(), entry.acc);
+}
+
+fn print_stop(debugger: &Debugger, stop: Stop) {
+    match stop {
+        Stop::Halted(halt) => println!("Halted: {:?}", halt),
+        Stop::Breakpoint(bp) => println!("Breakpoint: {:?}", bp),
+    }
+    print_state(debugger);
+}
+
+fn print_state(debugger: &Debugger) {
+    let machine = &debugger.vm.machine;
+    let next = match debugger.vm.current() {
+        Some(instr) => instr.to_string(),
+        None => String::from("-"),
+    };
+    println!("pc {}  registers {:?}  next {}", machine.pc, machine.registers, next);
+}
+
+fn print_loop(debugger: &Debugger) {
+    match debugger.loop_cycle() {
+        Some(pcs) => {
+            let pcs: Vec<_> = pcs.iter().map(|pc| pc.to_string()).collect();
+            println!("Loop: {} -> {}", pcs.join(" -> "), pcs[0]);
+        },
+        None => println!("No loop detected"),
+    }
+}
+
+fn run_debugger(program: &[Instruction]) {
+    let mut debugger = Debugger::new(Vm::new(program));
+    let stdin = io::stdin();
+    print!("(debug) ");
+    io::stdout().flush().unwrap();
+
+    for line in stdin.lock().lines() {
+        match parse_command(&line.unwrap()) {
+            Some(Command::Step(n)) => {
+                for _ in 0..n {
+                    if let Some(halt) = debugger.step() {
+                        println!("Halted: {:?}", halt);
+                        break;
+                    }
+                }
+                print_state(&debugger);
+            },
+            Some(Command::Continue) => {
+                let stop = debugger.resume();
+                print_stop(&debugger, stop);
+            },
+            Some(Command::Break(bp)) => debugger.breakpoints.push(bp),
+            Some(Command::Clear) => debugger.breakpoints.clear(),
+            Some(Command::Print) => print_state(&debugger),
+            Some(Command::Trace(n)) => {
+                let start = debugger.trace.len().saturating_sub(n);
+                debugger.trace[start..].iter().for_each(print_trace_entry);
+            },
+            Some(Command::Loop) => print_loop(&debugger),
+            Some(Command::Quit) => return,
+            None => println!("Commands: step [n], continue, break <pc>, break acc =|>|< <n>, clear, print, trace [n], loop, quit"),
+        }
+        print!("(debug) ");
+        io::stdout().flush().unwrap();
+    }
+}
+
+fn print_trace(program: &[Instruction]) {
+    let mut debugger = Debugger::new(Vm::new(program));
+    debugger.resume();
+    debugger.trace.iter().for_each(print_trace_entry);
+    print_loop(&debugger);
+}
+
+fn part1(program: &[Instruction]) {
+    let (_, machine) = run_program(program);
+    println!("Value before loop for part 1: {}", machine.acc());
+}
+
+fn part2(program: &[Instruction]) {
+    let repairs = find_repairs(program);
+    match repairs.split_first() {
+        Some((repair, others)) => {
+            println!("Value after terminate for part 2: {}", repair.acc);
+            for other in others {
+                println!("Also terminates flipping {:?}: {}", other.flips, other.acc);
+            }
+        },
+        None => println!("No single flip terminates the program"),
+    }
+}
+
+fn print_double_repairs(program: &[Instruction]) {
+    for repair in find_double_repairs(program) {
+        println!("Terminates flipping {:?}: {}", repair.flips, repair.acc);
+    }
+}
+
+/// Loads either an encoded program or source text, labels and all
+fn load_program(input: &[u8]) -> Result<Vec<Instruction>, AsmError> {
+    if input.starts_with(asm::MAGIC) {
+        return asm::decode(input);
+    }
+    return asm::parse_source(&String::from_utf8_lossy(input));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // The debugger reads commands from stdin, so the program comes from a file
+    let input = match args.iter().find_map(|a| a.strip_prefix("--debug=")) {
+        Some(path) => fs::read(path).unwrap(),
+        None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input).unwrap();
+            input
+        },
+    };
+    let program = match load_program(&input) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    };
+
+    if args.iter().any(|a| a.starts_with("--debug=")) {
+        run_debugger(&program);
+        return;
+    }
+    if args.iter().any(|a| a == "--assemble") {
+        io::stdout().write_all(&asm::encode(&program)).unwrap();
+        return;
+    }
+    if args.iter().any(|a| a == "--disassemble") {
+        program.iter().for_each(|instr| println!("{}", instr));
+        return;
+    }
+
+    part1(&program);
+    part2(&program);
+
+    if args.iter().any(|a| a == "--double") {
+        print_double_repairs(&program);
+    }
+    if args.iter().any(|a| a == "--trace") {
+        print_trace(&program);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("s"), Some(Command::Step(1)));
+        assert_eq!(parse_command("step 5"), Some(Command::Step(5)));
+        assert_eq!(parse_command("b 12"), Some(Command::Break(Breakpoint::Pc(12))));
+        assert_eq!(parse_command("break acc > -3"), Some(Command::Break(Breakpoint::AccAbove(-3))));
+        assert_eq!(parse_command("b acc == 7"), Some(Command::Break(Breakpoint::AccEquals(7))));
+        assert_eq!(parse_command("trace"), Some(Command::Trace(10)));
+        assert_eq!(parse_command("step x"), None);
+        assert_eq!(parse_command("jump"), None);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{Halt, Instruction, Vm};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub pc: isize,
+    pub instruction: Instruction,
+    /// Accumulator after the instruction ran
+    pub acc: isize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Breakpoint {
+    Pc(isize),
+    AccEquals(isize),
+    AccAbove(isize),
+    AccBelow(isize),
+}
+
+impl Breakpoint {
+    fn hit(&self, vm: &Vm) -> bool {
+        let acc = vm.machine.acc();
+        return match *self {
+            Breakpoint::Pc(pc) => vm.machine.pc == pc,
+            Breakpoint::AccEquals(value) => acc == value,
+            Breakpoint::AccAbove(value) => acc > value,
+            Breakpoint::AccBelow(value) => acc < value,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stop {
+    Halted(Halt),
+    Breakpoint(Breakpoint),
+}
+
+pub struct Debugger<'a> {
+    pub vm: Vm<'a>,
+    pub trace: Vec<TraceEntry>,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(vm: Vm<'a>) -> Self {
+        return Debugger {
+            vm,
+            trace: Vec::new(),
+            breakpoints: Vec::new(),
+        };
+    }
+
+    pub fn step(&mut self) -> Option<Halt> {
+        let pc = self.vm.machine.pc;
+        let instruction = self.vm.current();
+        let halt = self.vm.step();
+        if halt.is_none() {
+            self.trace.push(TraceEntry {
+                step: self.vm.steps,
+                pc,
+                instruction: instruction.unwrap(),
+                acc: self.vm.machine.acc(),
+            });
+        }
+        return halt;
+    }
+
+    /// Runs until the program halts or is about to run an instruction on a
+    /// breakpoint. Always executes at least one instruction, so resuming from
+    /// a breakpoint moves past it.
+    pub fn resume(&mut self) -> Stop {
+        if let Some(halt) = self.step() {
+            return Stop::Halted(halt);
+        }
+        loop {
+            if let Some(bp) = self.breakpoints.iter().find(|bp| bp.hit(&self.vm)) {
+                return Stop::Breakpoint(*bp);
+            }
+            if let Some(halt) = self.step() {
+                return Stop::Halted(halt);
+            }
+        }
+    }
+
+    /// The pcs making up the loop, in execution order, once the program is
+    /// about to re-run an instruction it has already visited
+    pub fn loop_cycle(&self) -> Option<Vec<isize>> {
+        if self.vm.halt_reason() != Some(Halt::InfiniteLoop) {
+            return None;
+        }
+        let pc = self.vm.machine.pc;
+        let start = self.trace.iter().rposition(|t| t.pc == pc)?;
+        return Some(self.trace[start..].iter().map(|t| t.pc).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn get_program() -> Vec<Instruction> {
+        return parse_program("nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6");
+    }
+
+    #[test]
+    fn test_trace() {
+        let program = get_program();
+        let mut debugger = Debugger::new(Vm::new(&program));
+        assert_eq!(debugger.resume(), Stop::Halted(Halt::InfiniteLoop));
+        let pcs: Vec<_> = debugger.trace.iter().map(|t| t.pc).collect();
+        assert_eq!(pcs, vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(debugger.trace[3], TraceEntry {
+            step: 4,
+            pc: 6,
+            instruction: Instruction::Acc(1),
+            acc: 2,
+        });
+    }
+
+    #[test]
+    fn test_loop_cycle() {
+        let program = get_program();
+        let mut debugger = Debugger::new(Vm::new(&program));
+        assert_eq!(debugger.loop_cycle(), None);
+        debugger.resume();
+        assert_eq!(debugger.loop_cycle(), Some(vec![1, 2, 6, 7, 3, 4]));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let program = get_program();
+        let mut debugger = Debugger::new(Vm::new(&program));
+        debugger.breakpoints.push(Breakpoint::Pc(7));
+        debugger.breakpoints.push(Breakpoint::AccAbove(4));
+
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Pc(7)));
+        assert_eq!(debugger.vm.machine.acc(), 2);
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::AccAbove(4)));
+        assert_eq!(debugger.vm.machine.pc, 4);
+        debugger.breakpoints.clear();
+        assert_eq!(debugger.resume(), Stop::Halted(Halt::InfiniteLoop));
+    }
+
+    #[test]
+    fn test_step() {
+        let program = parse_program("acc +3");
+        let mut debugger = Debugger::new(Vm::new(&program));
+        assert_eq!(debugger.step(), None);
+        assert_eq!(debugger.step(), Some(Halt::Terminated));
+        assert_eq!(debugger.trace.len(), 1);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashSet;
+use std::fmt;
+use Instruction::*;
+
+pub mod asm;
+pub mod debugger;
+pub mod repair;
+
+pub const NUM_REGISTERS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Instruction {
+    Acc(isize),
+    Jmp(isize),
+    Nop(isize),
+    Add(usize, isize),
+    Jz(usize, isize),
+    Jnz(usize, isize),
+}
+
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    /// Byte identifying the instruction in the binary encoding
+    pub code: u8,
+    pub has_register: bool,
+    pub build: fn(usize, isize) -> Instruction,
+}
+
+/// Every instruction the console understands, looked up by mnemonic or code
+pub const OPCODES: &[Opcode] = &[
+    Opcode {mnemonic: "acc", code: 0, has_register: false, build: |_, value| Acc(value)},
+    Opcode {mnemonic: "jmp", code: 1, has_register: false, build: |_, value| Jmp(value)},
+    Opcode {mnemonic: "nop", code: 2, has_register: false, build: |_, value| Nop(value)},
+    Opcode {mnemonic: "add", code: 3, has_register: true, build: Add},
+    Opcode {mnemonic: "jz", code: 4, has_register: true, build: Jz},
+    Opcode {mnemonic: "jnz", code: 5, has_register: true, build: Jnz},
+];
+
+impl Opcode {
+    pub fn from_mnemonic(mnemonic: &str) -> Option<&'static Opcode> {
+        return OPCODES.iter().find(|o| o.mnemonic == mnemonic);
+    }
+
+    pub fn from_code(code: u8) -> Option<&'static Opcode> {
+        return OPCODES.iter().find(|o| o.code == code);
+    }
+
+    /// Builds the instruction from its operands, using `value` to make sense
+    /// of the final one
+    pub fn parse<F: Fn(&str) -> Option<isize>>(&self, args: &[&str], value: F) -> Option<Instruction> {
+        let register = match self.has_register {
+            true => parse_register(args.first()?)?,
+            false => 0,
+        };
+        let rest = &args[self.has_register as usize..];
+        if rest.len() != 1 {
+            return None;
+        }
+        return Some((self.build)(register, value(rest[0])?));
+    }
+}
+
+pub fn parse_register(register_str: &str) -> Option<usize> {
+    let register: usize = register_str.strip_prefix('r')?.parse().ok()?;
+    return if register < NUM_REGISTERS { Some(register) } else { None };
+}
+
+impl Instruction {
+    pub fn from_str(instr_str: &str) -> Self {
+        let parts: Vec<_> = instr_str.split_whitespace().collect();
+        let opcode = parts.first().and_then(|m| Opcode::from_mnemonic(m));
+
+        match opcode.and_then(|o| o.parse(&parts[1..], |v| v.parse().ok())) {
+            Some(instr) => instr,
+            None => panic!("Unknown instruction: {}", instr_str),
+        }
+    }
+
+    pub fn opcode(&self) -> &'static Opcode {
+        let mnemonic = match self {
+            Acc(_) => "acc",
+            Jmp(_) => "jmp",
+            Nop(_) => "nop",
+            Add(..) => "add",
+            Jz(..) => "jz",
+            Jnz(..) => "jnz",
+        };
+        return Opcode::from_mnemonic(mnemonic).unwrap();
+    }
+
+    /// The register (0 where the instruction has none) and value operands
+    pub fn operands(&self) -> (usize, isize) {
+        return match *self {
+            Acc(value) | Jmp(value) | Nop(value) => (0, value),
+            Add(register, value) | Jz(register, value) | Jnz(register, value) => (register, value),
+        };
+    }
+
+    fn execute(&self, machine: &mut Machine) {
+        let mut offset = 1;
+        match *self {
+            Acc(value) => machine.registers[0] += value,
+            Jmp(value) => offset = value,
+            Nop(_) => (),
+            Add(register, value) => machine.registers[register] += value,
+            Jz(register, value) => if machine.registers[register] == 0 {
+                offset = value;
+            },
+            Jnz(register, value) => if machine.registers[register] != 0 {
+                offset = value;
+            },
+        }
+        machine.pc += offset;
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opcode = self.opcode();
+        let (register, value) = self.operands();
+        return match opcode.has_register {
+            true => write!(f, "{} r{} {:+}", opcode.mnemonic, register, value),
+            false => write!(f, "{} {:+}", opcode.mnemonic, value),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Machine {
+    pub pc: isize,
+    pub registers: [isize; NUM_REGISTERS],
+}
+
+impl Machine {
+    pub fn acc(&self) -> isize {
+        return self.registers[0];
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Halt {
+    Terminated,
+    InfiniteLoop,
+    OutOfBounds,
+    StepLimit,
+}
+
+/// How the VM decides that a program will never terminate
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopDetection {
+    /// Any instruction about to run a second time (the boot code rule)
+    Pc,
+    /// The whole machine state repeating, for programs with conditional jumps
+    State,
+}
+
+pub struct Vm<'a> {
+    program: &'a [Instruction],
+    pub machine: Machine,
+    pub steps: usize,
+    step_limit: Option<usize>,
+    loop_detection: LoopDetection,
+    /// Instructions run in place of the program's own at these pcs
+    patches: Vec<(usize, Instruction)>,
+    visited_pcs: Vec<bool>,
+    visited_states: HashSet<Machine>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a [Instruction]) -> Self {
+        return Vm {
+            program,
+            machine: Machine::default(),
+            steps: 0,
+            step_limit: None,
+            loop_detection: LoopDetection::Pc,
+            patches: Vec::new(),
+            visited_pcs: vec![false; program.len()],
+            visited_states: HashSet::new(),
+        };
+    }
+
+    pub fn with_machine(mut self, machine: Machine) -> Self {
+        self.machine = machine;
+        return self;
+    }
+
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = Some(step_limit);
+        return self;
+    }
+
+    pub fn with_loop_detection(mut self, loop_detection: LoopDetection) -> Self {
+        self.loop_detection = loop_detection;
+        return self;
+    }
+
+    /// Runs `instr` at `pc` instead of what the program has there, without
+    /// copying the program
+    pub fn with_patch(mut self, pc: usize, instr: Instruction) -> Self {
+        self.patches.retain(|&(patched, _)| patched != pc);
+        self.patches.push((pc, instr));
+        return self;
+    }
+
+    fn instruction(&self, pc: usize) -> Option<Instruction> {
+        return match self.patches.iter().find(|&&(patched, _)| patched == pc) {
+            Some(&(_, instr)) => Some(instr),
+            None => self.program.get(pc).copied(),
+        };
+    }
+
+    /// Why the machine can't execute another instruction, if it can't
+    pub fn halt_reason(&self) -> Option<Halt> {
+        let pc = self.machine.pc;
+        let size = self.program.len() as isize;
+        if pc == size {
+            return Some(Halt::Terminated);
+        }
+        if pc < 0 || pc > size {
+            return Some(Halt::OutOfBounds);
+        }
+        let repeated = match self.loop_detection {
+            LoopDetection::Pc => self.visited_pcs[pc as usize],
+            LoopDetection::State => self.visited_states.contains(&self.machine),
+        };
+        if repeated {
+            return Some(Halt::InfiniteLoop);
+        }
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Some(Halt::StepLimit);
+        }
+        return None;
+    }
+
+    pub fn step(&mut self) -> Option<Halt> {
+        if let Some(halt) = self.halt_reason() {
+            return Some(halt);
+        }
+        match self.loop_detection {
+            LoopDetection::Pc => self.visited_pcs[self.machine.pc as usize] = true,
+            LoopDetection::State => { self.visited_states.insert(self.machine); },
+        }
+        self.instruction(self.machine.pc as usize).unwrap().execute(&mut self.machine);
+        self.steps += 1;
+        return None;
+    }
+
+    /// The instruction at the current pc, if there is one
+    pub fn current(&self) -> Option<Instruction> {
+        if self.machine.pc < 0 {
+            return None;
+        }
+        return self.instruction(self.machine.pc as usize);
+    }
+
+    pub fn run(&mut self) -> Halt {
+        loop {
+            if let Some(halt) = self.step() {
+                return halt;
+            }
+        }
+    }
+}
+
+pub fn parse_program(input: &str) -> Vec<Instruction> {
+    let program = input.lines().map(|l| Instruction::from_str(l)).collect();
+    return program;
+}
+
+pub fn run_program(program: &[Instruction]) -> (Halt, Machine) {
+    let mut vm = Vm::new(program);
+    let halt = vm.run();
+    return (halt, vm.machine);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_input() -> &'static str {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+        return input;
+    }
+
+    #[test]
+    fn test_instruction_from_str_1() {
+        let instr = Instruction::from_str("nop +0");
+        assert_eq!(instr, Nop(0));
+    }
+
+    #[test]
+    fn test_instruction_from_str_2() {
+        let instr = Instruction::from_str("acc +1");
+        assert_eq!(instr, Acc(1));
+    }
+
+    #[test]
+    fn test_instruction_from_str_3() {
+        let instr = Instruction::from_str("jmp -3");
+        assert_eq!(instr, Jmp(-3));
+    }
+
+    #[test]
+    fn test_instruction_from_str_4() {
+        assert_eq!(Instruction::from_str("add r2 -7"), Add(2, -7));
+        assert_eq!(Instruction::from_str("jz r1 +3"), Jz(1, 3));
+        assert_eq!(Instruction::from_str("jnz r0 -1"), Jnz(0, -1));
+    }
+
+    #[test]
+    fn test_instruction_display() {
+        for instr_str in &["acc +1", "jmp -3", "nop +0", "add r2 -7", "jz r1 +3", "jnz r0 -1"] {
+            assert_eq!(Instruction::from_str(instr_str).to_string(), *instr_str);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_instruction_from_str_extra_operand() {
+        Instruction::from_str("acc +1 +2");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_instruction_from_str_bad_register() {
+        Instruction::from_str("add r9 +1");
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let input = get_input();
+        let program = parse_program(input);
+        let expected = vec![
+            Nop(0),
+            Acc(1),
+            Jmp(4),
+            Acc(3),
+            Jmp(-3),
+            Acc(-99),
+            Acc(1),
+            Jmp(-4),
+            Acc(6),
+        ];
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_run_program_1() {
+        let input = get_input();
+        let program = parse_program(input);
+        let (halt, machine) = run_program(&program);
+        assert_eq!(halt, Halt::InfiniteLoop);
+        assert_eq!(machine.acc(), 5);
+        assert_eq!(machine.pc, 1);
+    }
+
+    #[test]
+    fn test_run_program_2() {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+nop -4
+acc +6";
+        let program = parse_program(input);
+        let (halt, machine) = run_program(&program);
+        assert_eq!(halt, Halt::Terminated);
+        assert_eq!(machine.acc(), 8);
+    }
+
+    #[test]
+    fn test_run_program_out_of_bounds() {
+        let program = parse_program("acc +1\njmp +5");
+        assert_eq!(run_program(&program).0, Halt::OutOfBounds);
+        let program = parse_program("jmp -1");
+        assert_eq!(run_program(&program).0, Halt::OutOfBounds);
+    }
+
+    #[test]
+    fn test_vm_step_limit() {
+        let program = parse_program("acc +1\nacc +1\nacc +1");
+        let mut vm = Vm::new(&program).with_step_limit(2);
+        assert_eq!(vm.run(), Halt::StepLimit);
+        assert_eq!(vm.machine.acc(), 2);
+        assert_eq!(vm.steps, 2);
+    }
+
+    #[test]
+    fn test_vm_conditional_loop() {
+        // Count r1 down from 3, adding 2 to the accumulator each time round
+        let program = parse_program("add r1 +3
+acc +2
+add r1 -1
+jnz r1 -2");
+        let mut vm = Vm::new(&program).with_loop_detection(LoopDetection::State);
+        assert_eq!(vm.run(), Halt::Terminated);
+        assert_eq!(vm.machine.acc(), 6);
+        assert_eq!(vm.machine.registers[1], 0);
+
+        let mut vm = Vm::new(&program);
+        assert_eq!(vm.run(), Halt::InfiniteLoop);
+    }
+
+    #[test]
+    fn test_vm_patch() {
+        let program = parse_program(get_input());
+        let mut vm = Vm::new(&program).with_patch(7, Nop(-4));
+        assert_eq!(vm.run(), Halt::Terminated);
+        assert_eq!(vm.machine.acc(), 8);
+        assert_eq!(program[7], Jmp(-4));
+    }
+
+    #[test]
+    fn test_vm_state_loop() {
+        let program = parse_program("jz r1 +0");
+        let mut vm = Vm::new(&program).with_loop_detection(LoopDetection::State);
+        assert_eq!(vm.run(), Halt::InfiniteLoop);
+        assert_eq!(vm.steps, 1);
+    }
+}
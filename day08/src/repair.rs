@@ -0,0 +1,330 @@
+use std::collections::VecDeque;
+use crate::{Halt, Instruction, Vm};
+use crate::Instruction::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Repair {
+    /// The pcs whose jmp/nop were swapped
+    pub flips: Vec<usize>,
+    /// Accumulator when the repaired program terminates
+    pub acc: isize,
+}
+
+fn flipped(instr: Instruction) -> Option<Instruction> {
+    return match instr {
+        Jmp(value) => Some(Nop(value)),
+        Nop(value) => Some(Jmp(value)),
+        _ => None,
+    };
+}
+
+/// Every pc the instruction may move to next. Conditional jumps are assumed
+/// to be able to go either way.
+fn successors(instr: Instruction, pc: usize) -> Vec<isize> {
+    let pc = pc as isize;
+    return match instr {
+        Jmp(offset) => vec![pc + offset],
+        Jz(_, offset) | Jnz(_, offset) => vec![pc + 1, pc + offset],
+        _ => vec![pc + 1],
+    };
+}
+
+/// Which pcs can go on to terminate, indexed up to and including
+/// `program.len()`, the terminating pc itself
+pub fn terminating_pcs(program: &[Instruction]) -> Vec<bool> {
+    return terminating_pcs_flipped(program, None);
+}
+
+/// Every pc that may move to each pc next, with the jmp/nop at `flip` swapped
+fn predecessors(program: &[Instruction], flip: Option<usize>) -> Vec<Vec<usize>> {
+    let size = program.len();
+    let mut predecessors = vec![Vec::new(); size + 1];
+    for (pc, &instr) in program.iter().enumerate() {
+        let instr = match flip {
+            Some(flip) if flip == pc => flipped(instr).unwrap_or(instr),
+            _ => instr,
+        };
+        for next in successors(instr, pc) {
+            if next >= 0 && next as usize <= size {
+                predecessors[next as usize].push(pc);
+            }
+        }
+    }
+    return predecessors;
+}
+
+/// Same as `terminating_pcs`, with the jmp/nop at `flip` swapped
+fn terminating_pcs_flipped(program: &[Instruction], flip: Option<usize>) -> Vec<bool> {
+    let size = program.len();
+    let predecessors = predecessors(program, flip);
+    let mut terminating = vec![false; size + 1];
+    let mut queue = VecDeque::new();
+    queue.push_back(size);
+    while let Some(pc) = queue.pop_front() {
+        if terminating[pc] {
+            continue;
+        }
+        terminating[pc] = true;
+        queue.extend(predecessors[pc].iter().copied());
+    }
+
+    return terminating;
+}
+
+fn leads_to_termination(terminating: &[bool], pc: isize) -> bool {
+    return pc >= 0 && terminating.get(pc as usize).copied().unwrap_or(false);
+}
+
+/// How much the instruction adds to the accumulator, which is register 0
+fn acc_change(instr: Instruction) -> isize {
+    return match instr {
+        Acc(value) | Add(0, value) => value,
+        _ => 0,
+    };
+}
+
+fn has_conditional_jumps(program: &[Instruction]) -> bool {
+    return program.iter().any(|instr| matches!(instr, Jz(..) | Jnz(..)));
+}
+
+/// For each pc that can go on to terminate, how much the accumulator grows on
+/// the way, found in one pass back from the end. Without conditional jumps
+/// every pc has only one way forward, so this is exact.
+fn acc_to_end(program: &[Instruction]) -> Vec<Option<isize>> {
+    let size = program.len();
+    let predecessors = predecessors(program, None);
+    let mut gains = vec![None; size + 1];
+    gains[size] = Some(0);
+    let mut queue = VecDeque::new();
+    queue.push_back(size);
+    while let Some(pc) = queue.pop_front() {
+        let gain = gains[pc].unwrap();
+        for &previous in &predecessors[pc] {
+            if gains[previous].is_none() {
+                gains[previous] = Some(acc_change(program[previous]) + gain);
+                queue.push_back(previous);
+            }
+        }
+    }
+    return gains;
+}
+
+/// The pcs a program runs, with the jmp/nops at `flips` swapped, before it
+/// halts, and how it halts
+fn execution_path(program: &[Instruction], flips: &[usize]) -> (Vec<usize>, Halt) {
+    let mut vm = flipped_vm(program, flips);
+    let mut path = Vec::new();
+    loop {
+        if let Some(halt) = vm.halt_reason() {
+            return (path, halt);
+        }
+        path.push(vm.machine.pc as usize);
+        vm.step();
+    }
+}
+
+fn flipped_vm<'a>(program: &'a [Instruction], flips: &[usize]) -> Vm<'a> {
+    let mut vm = Vm::new(program);
+    for &pc in flips {
+        if let Some(instr) = flipped(program[pc]) {
+            vm = vm.with_patch(pc, instr);
+        }
+    }
+    return vm;
+}
+
+/// Runs the program with the jmp/nops at `flips` swapped over in the VM
+/// rather than in a copy of the program
+fn try_repair(program: &[Instruction], flips: &[usize]) -> Option<Repair> {
+    let mut vm = flipped_vm(program, flips);
+    return match vm.run() {
+        Halt::Terminated => Some(Repair {flips: flips.to_vec(), acc: vm.machine.acc()}),
+        _ => None,
+    };
+}
+
+/// Whether swapping the instruction at `pc` sends it somewhere that can
+/// reach the end
+fn flip_can_terminate(program: &[Instruction], terminating: &[bool], pc: usize) -> bool {
+    return match flipped(program[pc]) {
+        Some(instr) => successors(instr, pc).into_iter()
+            .any(|next| leads_to_termination(terminating, next)),
+        None => false,
+    };
+}
+
+/// Finds every single jmp/nop swap that makes a looping program terminate.
+///
+/// Only instructions on the looping execution path can matter, and swapping
+/// one of them works exactly when its new target can already reach the end:
+/// that way never leads back through the path, or the program wouldn't loop.
+/// The accumulator is then what the path built up before the swap plus what
+/// the rest of the way to the end adds, so one pass over the path finds
+/// every repair. Conditional jumps depend on registers that this can't
+/// follow, so with those each candidate is checked by running it instead.
+pub fn find_repairs(program: &[Instruction]) -> Vec<Repair> {
+    let (path, halt) = execution_path(program, &[]);
+    if halt == Halt::Terminated {
+        return Vec::new();
+    }
+    return single_repairs(program, &path);
+}
+
+fn single_repairs(program: &[Instruction], path: &[usize]) -> Vec<Repair> {
+    if has_conditional_jumps(program) {
+        let terminating = terminating_pcs(program);
+        return path.iter()
+            .filter(|&&pc| flip_can_terminate(program, &terminating, pc))
+            .filter_map(|&pc| try_repair(program, &[pc]))
+            .collect();
+    }
+
+    let gains = acc_to_end(program);
+    let mut repairs = Vec::new();
+    let mut acc = 0;
+    for &pc in path {
+        if let Some(instr) = flipped(program[pc]) {
+            let target = successors(instr, pc)[0];
+            let gain = if target >= 0 { gains.get(target as usize).copied().flatten() } else { None };
+            if let Some(gain) = gain {
+                repairs.push(Repair {flips: vec![pc], acc: acc + gain});
+            }
+        }
+        acc += acc_change(program[pc]);
+    }
+    return repairs;
+}
+
+/// Finds every pair of jmp/nop swaps that makes a looping program terminate
+/// where neither swap would do it alone
+pub fn find_double_repairs(program: &[Instruction]) -> Vec<Repair> {
+    let (path, halt) = execution_path(program, &[]);
+    if halt == Halt::Terminated {
+        return Vec::new();
+    }
+    let singles: Vec<usize> = single_repairs(program, &path).into_iter().map(|r| r.flips[0]).collect();
+    let mut repairs = Vec::new();
+
+    for &first in &path {
+        if flipped(program[first]).is_none() || singles.contains(&first) {
+            continue;
+        }
+
+        // The second swap has to be somewhere the program runs after the
+        // first, and has to be able to reach the end with the first in place
+        let terminating = terminating_pcs_flipped(program, Some(first));
+        let (flipped_path, _) = execution_path(program, &[first]);
+        let after_first = flipped_path.iter().skip_while(|&&pc| pc != first).skip(1);
+        for &second in after_first {
+            if singles.contains(&second) || !flip_can_terminate(program, &terminating, second) {
+                continue;
+            }
+            repairs.extend(try_repair(program, &[first, second]));
+        }
+    }
+
+    return repairs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_terminating_pcs() {
+        let program = parse_program("nop +0
+jmp +0
+acc +1");
+        assert_eq!(terminating_pcs(&program), vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn test_find_repairs() {
+        let program = parse_program("nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6");
+        assert_eq!(find_repairs(&program), vec![Repair {flips: vec![7], acc: 8}]);
+    }
+
+    #[test]
+    fn test_find_repairs_multiple() {
+        let program = parse_program("nop +3
+acc +1
+jmp +0
+acc +5");
+        let repairs = find_repairs(&program);
+        assert_eq!(repairs, vec![
+            Repair {flips: vec![0], acc: 5},
+            Repair {flips: vec![2], acc: 6},
+        ]);
+    }
+
+    #[test]
+    fn test_find_repairs_registers() {
+        // Adding to r0 changes the accumulator, and other registers don't
+        let program = parse_program("nop +2
+add r0 +5
+jmp +0
+add r0 -2
+add r1 +7
+acc +1");
+        assert_eq!(find_repairs(&program), vec![Repair {flips: vec![2], acc: 4}]);
+    }
+
+    #[test]
+    fn test_find_repairs_conditional() {
+        // Swapping the jmp at pc 2 reaches the jnz, which could jump to the
+        // end, but with r1 at zero it never does, so that swap still loops
+        let program = parse_program("nop +0
+jnz r1 +3
+jmp -2
+jmp -2
+acc +2");
+        assert!(find_repairs(&program).is_empty());
+    }
+
+    #[test]
+    fn test_find_repairs_terminating() {
+        let program = parse_program("acc +1\nnop +0");
+        assert!(find_repairs(&program).is_empty());
+        assert!(find_double_repairs(&program).is_empty());
+    }
+
+    #[test]
+    fn test_find_double_repairs() {
+        let program = parse_program("nop +3
+jmp -1
+acc +100
+jmp +0
+acc +1");
+        assert!(find_repairs(&program).is_empty());
+        assert_eq!(find_double_repairs(&program), vec![
+            Repair {flips: vec![0, 3], acc: 1},
+            Repair {flips: vec![1, 3], acc: 101},
+        ]);
+    }
+
+    #[test]
+    fn test_find_double_repairs_conditional() {
+        // With r1 at zero the jz always jumps, so the second swap has to be
+        // at pc 4 rather than on the fall-through at pc 3
+        let program = parse_program("nop +2
+jmp +0
+jz r1 +2
+jmp +0
+jmp +0
+acc +1");
+        assert!(find_repairs(&program).is_empty());
+        assert_eq!(find_double_repairs(&program), vec![
+            Repair {flips: vec![0, 4], acc: 1},
+            Repair {flips: vec![1, 4], acc: 1},
+        ]);
+    }
+}
@@ -1,40 +1,106 @@
-use std::io::{self, Read};
-use day01::find_entries;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::io::{self, BufRead};
+use std::ops::RangeInclusive;
 
-const PREV_ENTRIES: u8 = 25;
+const PREV_ENTRIES: usize = 25;
 
-fn find_contiguous_sum(entries: &[u64], num: u64) -> Option<&[u64]> {
-    for n in 2..entries.len() {
-        for i in n..entries.len() {
-            let set = &entries[i-n..i];
-            let sum: u64 = set.iter().sum();
-            if sum == num {
-                return Some(set);
+/// Checks numbers one at a time against the window of numbers before them
+struct Validator {
+    window: usize,
+    recent: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+}
+
+impl Validator {
+    fn new(window: usize) -> Self {
+        return Validator {
+            window,
+            recent: VecDeque::with_capacity(window + 1),
+            counts: HashMap::new(),
+        };
+    }
+
+    fn is_sum(&self, number: u64) -> bool {
+        return self.recent.iter().any(|&a| {
+            match number.checked_sub(a) {
+                Some(b) if b == a => self.counts[&a] > 1,
+                Some(b) => self.counts.contains_key(&b),
+                None => false,
+            }
+        });
+    }
+
+    /// Adds the next number, returning whether it's valid. Numbers in the
+    /// preamble are always valid.
+    fn push(&mut self, number: u64) -> bool {
+        let valid = self.recent.len() < self.window || self.is_sum(number);
+
+        self.recent.push_back(number);
+        *self.counts.entry(number).or_insert(0) += 1;
+        if self.recent.len() > self.window {
+            let old = self.recent.pop_front().unwrap();
+            let count = self.counts.get_mut(&old).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
             }
         }
+
+        return valid;
     }
-    return None;
 }
 
-fn find_number(entries: &[u64], prev_n: u8) -> Option<u64> {
-    let n = prev_n as usize;
-    for i in n..entries.len() {
-        let number = entries[i];
-        match find_entries(&entries[i-n..i], number) {
-            Some(_) => (),
-            None => return Some(number),
+/// Every run of at least two entries that adds up to `num`, as start and end
+/// indices
+fn find_contiguous_ranges(entries: &[u64], num: u64) -> Vec<RangeInclusive<usize>> {
+    // Numbers are never negative, so the sum only grows as the end moves
+    // right and only shrinks as the start does
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut sum = 0;
+    for end in 0..entries.len() {
+        sum += entries[end];
+        while sum > num && start <= end {
+            sum -= entries[start];
+            start += 1;
+        }
+        // Leading zeros can be dropped without changing the sum
+        let mut first = start;
+        while sum == num && end + 1 - first >= 2 {
+            ranges.push(first..=end);
+            if entries[first] != 0 {
+                break;
+            }
+            first += 1;
         }
     }
-    return None;
+    return ranges;
+}
+
+fn find_contiguous_sum(entries: &[u64], num: u64) -> Option<&[u64]> {
+    return find_contiguous_ranges(entries, num).into_iter().next().map(|r| &entries[r]);
+}
+
+/// Numbers that aren't the sum of two of the `prev_n` before them, with their
+/// indices. Each one comes out as soon as it's read, so this can sit on top
+/// of input that's still arriving.
+fn invalid_numbers<I: Iterator<Item = u64>>(numbers: I,
+        prev_n: usize) -> impl Iterator<Item = (usize, u64)> {
+    let mut validator = Validator::new(prev_n);
+    return numbers.enumerate().filter(move |&(_, number)| !validator.push(number));
 }
 
-fn parse_entries(input: &str) -> Vec<u64> {
-    let entries: Vec<u64> = input.lines().map(|l| l.parse().unwrap()).collect();
-    return entries;
+fn find_invalid_numbers(entries: &[u64], prev_n: usize) -> Vec<(usize, u64)> {
+    return invalid_numbers(entries.iter().copied(), prev_n).collect();
 }
 
-fn part1(entries: &[u64]) -> u64 {
-    let number = find_number(entries, PREV_ENTRIES).unwrap();
+fn parse_entries<R: BufRead>(input: R) -> impl Iterator<Item = u64> {
+    return input.lines().map(|l| l.unwrap().trim().parse().unwrap());
+}
+
+fn part1(first_invalid: Option<u64>) -> u64 {
+    let number = first_invalid.unwrap();
     println!("Number for part 1: {}", number);
     return number;
 }
@@ -47,13 +113,36 @@ fn part2(entries: &[u64], number: u64) {
     println!("Number for part 2: {}", result);
 }
 
+fn print_audit(entries: &[u64], window: usize) {
+    for (index, number) in find_invalid_numbers(entries, window) {
+        println!("Invalid number at index {}: {}", index, number);
+        for range in find_contiguous_ranges(entries, number) {
+            println!("  Sums to it: indices {}..={}", range.start(), range.end());
+        }
+    }
+}
+
 fn main() {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input).unwrap();
-    let entries = parse_entries(&input);
+    let window = env::args()
+        .find_map(|a| a.strip_prefix("--window=").map(|w| w.parse().unwrap()))
+        .unwrap_or(PREV_ENTRIES);
+
+    // Flag invalid numbers as they arrive rather than waiting for the whole
+    // input, keeping every number for part 2
+    let mut entries = Vec::new();
+    let mut first_invalid = None;
+    let numbers = parse_entries(io::stdin().lock()).inspect(|&number| entries.push(number));
+    for (index, number) in invalid_numbers(numbers, window) {
+        eprintln!("Invalid number at index {}: {}", index, number);
+        first_invalid.get_or_insert(number);
+    }
 
-    let number = part1(&entries);
+    let number = part1(first_invalid);
     part2(&entries, number);
+
+    if env::args().any(|a| a == "--audit") {
+        print_audit(&entries, window);
+    }
 }
 
 #[cfg(test)]
@@ -64,8 +153,48 @@ mod tests {
     fn test_find_number() {
         let entries = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117,
             150, 182, 127, 219, 299, 277, 309, 576];
-        let number = find_number(&entries, 5);
-        assert_eq!(number, Some(127));
+        let number = invalid_numbers(entries.into_iter(), 5).next();
+        assert_eq!(number, Some((14, 127)));
+    }
+
+    #[test]
+    fn test_find_invalid_numbers() {
+        let entries = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117,
+            150, 182, 127, 219, 299, 277, 309, 576];
+        assert_eq!(find_invalid_numbers(&entries, 5), vec![(14, 127)]);
+        assert_eq!(find_invalid_numbers(&entries, 2), vec![
+            (2, 15), (3, 25), (4, 47), (5, 40), (6, 62), (7, 55), (8, 65), (9, 95),
+            (10, 102), (11, 117), (12, 150), (13, 182), (14, 127), (15, 219),
+            (16, 299), (17, 277), (18, 309), (19, 576),
+        ]);
+    }
+
+    #[test]
+    fn test_find_contiguous_ranges() {
+        let entries = vec![1, 2, 3, 0, 3, 2, 1];
+        assert_eq!(find_contiguous_ranges(&entries, 5), vec![1..=2, 1..=3, 3..=5, 4..=5]);
+        assert_eq!(find_contiguous_ranges(&entries, 12), vec![0..=6]);
+        assert!(find_contiguous_ranges(&entries, 13).is_empty());
+    }
+
+    #[test]
+    fn test_validator() {
+        let mut validator = Validator::new(3);
+        let results: Vec<_> = [1, 2, 3, 3, 6, 4, 12, 10]
+            .iter().map(|&n| validator.push(n)).collect();
+        assert_eq!(results, vec![true, true, true, true, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_validator_repeated() {
+        let mut validator = Validator::new(2);
+        assert!(validator.push(5));
+        assert!(validator.push(7));
+        assert!(!validator.push(10));
+        let mut validator = Validator::new(2);
+        assert!(validator.push(5));
+        assert!(validator.push(5));
+        assert!(validator.push(10));
     }
 
     #[test]
@@ -90,7 +219,7 @@ mod tests {
 277
 309
 576";
-        let actual = parse_entries(input);
+        let actual: Vec<_> = parse_entries(input.as_bytes()).collect();
         let expected = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117,
             150, 182, 127, 219, 299, 277, 309, 576];
         assert_eq!(actual, expected);
@@ -104,4 +233,12 @@ mod tests {
         let expected = vec![15, 25, 47, 40];
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_find_contiguous_sum_edges() {
+        assert_eq!(find_contiguous_sum(&[5, 1, 2, 3], 5), Some(&[2, 3][..]));
+        assert_eq!(find_contiguous_sum(&[1, 2, 3, 4], 10), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(find_contiguous_sum(&[0, 7, 1], 7), Some(&[0, 7][..]));
+        assert_eq!(find_contiguous_sum(&[7, 9], 8), None);
+    }
 }
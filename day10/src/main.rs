@@ -1,44 +1,343 @@
-use std::collections::HashMap;
+use num_bigint::BigUint;
+use std::env;
+use std::fmt;
 use std::io::{self, Read};
+use std::process;
 
-fn calculate_differences(chain: &[u16]) -> Option<(u16, u16, u16)> {
-    let mut counts = (0, 0, 0);
+#[derive(Debug, PartialEq, Eq)]
+enum ChainError {
+    /// A maximum step of zero, which nothing can fit
+    ZeroMaxStep,
+    /// The device would be rated above what a `u16` holds, over this adapter
+    DeviceOverflow(u16),
+    /// Nothing fits between these two joltages
+    Gap(u16, u16),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ChainError::ZeroMaxStep => write!(f, "The maximum step must be at least 1"),
+            ChainError::DeviceOverflow(highest) => {
+                write!(f, "The device's joltage overflows above the {} jolt adapter", highest)
+            },
+            ChainError::Gap(from, to) => write!(f, "Nothing fits between {} and {} jolts", from, to),
+        };
+    }
+}
+
+/// How adapters may be chained between the outlet and the device
+struct AdapterModel {
+    outlet: u16,
+    min_step: u16,
+    max_step: u16,
+    /// How far above the highest adapter the device's own adapter is rated
+    device_offset: u16,
+    /// Adapters that fail this check are left out of every chain
+    rule: Option<Box<dyn Fn(u16) -> bool>>,
+}
+
+impl Default for AdapterModel {
+    fn default() -> Self {
+        return AdapterModel {
+            outlet: 0,
+            min_step: 1,
+            max_step: 3,
+            device_offset: 3,
+            rule: None,
+        };
+    }
+}
+
+impl AdapterModel {
+    fn fits(&self, from: u16, to: u16) -> bool {
+        return to >= from && (self.min_step..=self.max_step).contains(&(to - from));
+    }
+
+    fn usable(&self, chain: &[u16]) -> Vec<u16> {
+        return chain.iter().copied()
+            .filter(|&a| a > self.outlet && self.rule.as_ref().is_none_or(|rule| rule(a)))
+            .collect();
+    }
+
+    /// The device's joltage, `device_offset` above the highest usable adapter
+    fn device(&self, chain: &[u16]) -> Result<u16, ChainError> {
+        if self.max_step == 0 {
+            return Err(ChainError::ZeroMaxStep);
+        }
+        let highest = self.usable(chain).last().copied().unwrap_or(self.outlet);
+        return highest.checked_add(self.device_offset).ok_or(ChainError::DeviceOverflow(highest));
+    }
+}
+
+/// Histogram of the steps between consecutive adapters, indexed by step size
+fn calculate_differences(chain: &[u16], model: &AdapterModel) -> Result<Vec<u16>, ChainError> {
+    let device = model.device(chain)?;
+    let mut counts = vec![0; model.max_step as usize + 1];
 
-    let mut prev = 0;
-    for adapter in chain {
-        let diff = adapter - prev;
-        match diff {
-            1 => counts.0 += 1,
-            2 => counts.1 += 1,
-            3 => counts.2 += 1,
-            _ => return None,
+    let mut prev = model.outlet;
+    for adapter in model.usable(chain).into_iter().chain(Some(device)) {
+        if !model.fits(prev, adapter) {
+            return Err(ChainError::Gap(prev, adapter));
         }
-        prev = *adapter;
+        counts[(adapter - prev) as usize] += 1;
+        prev = adapter;
+    }
+
+    return Ok(counts);
+}
+
+/// One row of the arrangement table: how many ways there are to reach this
+/// joltage and which earlier rows they come from
+#[derive(Debug, PartialEq, Eq)]
+struct ChainStep {
+    joltage: u16,
+    ways: BigUint,
+    from: Vec<usize>,
+}
+
+/// Builds the arrangement counts bottom-up, starting with the outlet
+fn chain_table(chain: &[u16], model: &AdapterModel) -> Vec<ChainStep> {
+    let mut table = vec![ChainStep {
+        joltage: model.outlet,
+        ways: BigUint::from(1u32),
+        from: Vec::new(),
+    }];
+
+    for adapter in model.usable(chain) {
+        // Only the rows within a step of this adapter can lead to it, so
+        // each row looks back over at most `max_step` joltages
+        let reachable: Vec<(usize, &ChainStep)> = table.iter().enumerate().rev()
+            .take_while(|(_, step)| adapter - step.joltage <= model.max_step)
+            .filter(|(_, step)| model.fits(step.joltage, adapter))
+            .collect();
+        let ways = reachable.iter().map(|(_, step)| &step.ways).sum();
+        let from = reachable.iter().rev().map(|&(row, _)| row).collect();
+        table.push(ChainStep {joltage: adapter, ways, from});
     }
-    counts.2 += 1;
 
-    return Some(counts);
+    return table;
+}
+
+fn count_chains(chain: &[u16], model: &AdapterModel) -> Result<BigUint, ChainError> {
+    let device = model.device(chain)?;
+    // The device can go after any joltage close enough below it, not just
+    // the highest adapter, when its offset is smaller than the largest step
+    return Ok(chain_table(chain, model).iter().rev()
+        .take_while(|step| device - step.joltage <= model.max_step)
+        .filter(|step| model.fits(step.joltage, device))
+        .map(|step| &step.ways)
+        .sum());
+}
+
+fn format_table(table: &[ChainStep]) -> String {
+    let mut out = format!("{:>8}  {:>20}  From\n", "Joltage", "Ways");
+    for step in table {
+        let from: Vec<_> = step.from.iter().map(|&row| table[row].joltage.to_string()).collect();
+        out.push_str(&format!("{:>8}  {:>20}  {}\n", step.joltage, step.ways, from.join(", ")));
+    }
+    return out;
 }
 
-fn count_chains(chain: &[u16]) -> u64 {
-    let mut cache = HashMap::new();
-    return count_chains_rec(chain, 0, &mut cache);
+/// Every valid chain through the adapters, indexed in lexicographic order.
+/// A chain that stops where the device can go next comes before the longer
+/// chains carrying on from it.
+struct ChainSpace {
+    /// The rows of the arrangement table, the outlet first
+    joltages: Vec<u16>,
+    /// Rows each row can go on to, lowest first
+    next: Vec<Vec<usize>>,
+    /// Whether the device can go straight after each row
+    stops: Vec<bool>,
+    /// How many ways each row can go on to reach the device
+    ways_from: Vec<BigUint>,
 }
 
-fn count_chains_rec(chain: &[u16], prev: u16, cache: &mut HashMap<u16, u64>) -> u64 {
-    match cache.get(&prev) {
-        Some(&count) => count,
-        None => {
-            if chain.len() < 2 {
-                return 1;
+impl ChainSpace {
+    fn new(chain: &[u16], model: &AdapterModel) -> Result<Self, ChainError> {
+        let device = model.device(chain)?;
+        let table = chain_table(chain, model);
+        let joltages: Vec<u16> = table.iter().map(|step| step.joltage).collect();
+        let mut next = vec![Vec::new(); table.len()];
+        for (row, step) in table.iter().enumerate() {
+            for &from in &step.from {
+                next[from].push(row);
             }
-            let mut count = 0;
-            for (i, adapter) in chain.iter().take_while(|a| *a - prev <= 3).enumerate() {
-                count += count_chains_rec(&chain[i+1..], *adapter, cache);
+        }
+        let stops: Vec<bool> = joltages.iter().map(|&j| model.fits(j, device)).collect();
+
+        // The table counts ways in from the outlet, so run the same links
+        // backwards to count ways on to the device
+        let mut ways_from = vec![BigUint::default(); table.len()];
+        for row in (0..table.len()).rev() {
+            let mut ways: BigUint = next[row].iter().map(|&n| &ways_from[n]).sum();
+            if stops[row] {
+                ways += 1u32;
             }
-            cache.insert(prev, count);
-            return count;
-        },
+            ways_from[row] = ways;
+        }
+
+        return Ok(ChainSpace {joltages, next, stops, ways_from});
+    }
+
+    fn total(&self) -> &BigUint {
+        return &self.ways_from[0];
+    }
+
+    /// Next steps from `row` that still lead to the device, lowest first
+    fn successors(&self, row: usize) -> impl Iterator<Item = usize> + '_ {
+        return self.next[row].iter().copied()
+            .filter(move |&n| self.ways_from[n] > BigUint::default());
+    }
+
+    /// The adapters along a path of rows, leaving out the outlet
+    fn to_chain(&self, path: &[usize]) -> Vec<u16> {
+        return path.iter().skip(1).map(|&row| self.joltages[row]).collect();
+    }
+
+    fn iter(&self) -> Chains<'_> {
+        return Chains {space: self, stack: Vec::new(), started: false};
+    }
+
+    /// The chain at position `index` in lexicographic order
+    fn nth(&self, index: &BigUint) -> Option<Vec<u16>> {
+        if index >= self.total() {
+            return None;
+        }
+        let mut index = index.clone();
+        let mut path = vec![0];
+        loop {
+            let current = *path.last().unwrap();
+            if self.stops[current] {
+                if index == BigUint::default() {
+                    return Some(self.to_chain(&path));
+                }
+                index -= 1u32;
+            }
+            for next in self.successors(current) {
+                if index < self.ways_from[next] {
+                    path.push(next);
+                    break;
+                }
+                index -= &self.ways_from[next];
+            }
+        }
+    }
+
+    /// Picks `count` chains uniformly at random, reproducibly for a seed
+    fn sample(&self, seed: u64, count: usize) -> Vec<Vec<u16>> {
+        let mut rng = SplitMix64(seed);
+        return (0..count)
+            .filter_map(|_| self.nth(&rng.below(self.total())?))
+            .collect();
+    }
+
+    /// The chain using the fewest adapters, or with `longest` the most.
+    /// Ties go to the lexicographically smallest chain.
+    fn extreme(&self, longest: bool) -> Option<Vec<u16>> {
+        let rows = self.joltages.len();
+        let mut lengths: Vec<Option<usize>> = vec![None; rows];
+        // Where each row goes next on the best chain, or `None` to stop there
+        let mut best_next = vec![None; rows];
+        for row in (0..rows).rev() {
+            if self.stops[row] {
+                lengths[row] = Some(0);
+            }
+            for next in self.successors(row) {
+                let length = lengths[next].unwrap() + 1;
+                let better = match lengths[row] {
+                    None => true,
+                    Some(best) if longest => length > best,
+                    Some(best) => length < best,
+                };
+                if better {
+                    lengths[row] = Some(length);
+                    best_next[row] = Some(next);
+                }
+            }
+        }
+
+        lengths[0]?;
+        let mut path = vec![0];
+        while let Some(next) = best_next[*path.last().unwrap()] {
+            path.push(next);
+        }
+        return Some(self.to_chain(&path));
+    }
+}
+
+/// Lazily walks every chain in lexicographic order
+struct Chains<'s> {
+    space: &'s ChainSpace,
+    /// Rows along the current chain, each with how many of its successors
+    /// have been tried
+    stack: Vec<(usize, usize)>,
+    started: bool,
+}
+
+impl Chains<'_> {
+    fn current(&self) -> Vec<u16> {
+        let path: Vec<usize> = self.stack.iter().map(|&(row, _)| row).collect();
+        return self.space.to_chain(&path);
+    }
+}
+
+impl Iterator for Chains<'_> {
+    type Item = Vec<u16>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if self.space.total() == &BigUint::default() {
+                return None;
+            }
+            self.stack.push((0, 0));
+            if self.space.stops[0] {
+                return Some(self.current());
+            }
+        }
+
+        // Go down into the next untried successor, backing up when a row
+        // has none left, until reaching somewhere the chain can stop
+        while let Some((row, tried)) = self.stack.pop() {
+            let next = self.space.successors(row).nth(tried);
+            if let Some(next) = next {
+                self.stack.push((row, tried + 1));
+                self.stack.push((next, 0));
+                if self.space.stops[next] {
+                    return Some(self.current());
+                }
+            }
+        }
+        return None;
+    }
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+
+    /// Uniform below `bound` by rejection sampling, or `None` for a zero bound
+    fn below(&mut self, bound: &BigUint) -> Option<BigUint> {
+        if *bound == BigUint::default() {
+            return None;
+        }
+        let bits = bound.bits();
+        let digits = bits.div_ceil(32);
+        loop {
+            let random: Vec<u32> = (0..digits).map(|_| self.next_u64() as u32).collect();
+            let candidate = BigUint::from_slice(&random) >> (digits * 32 - bits);
+            if candidate < *bound {
+                return Some(candidate);
+            }
+        }
     }
 }
 
@@ -54,25 +353,100 @@ fn prepare_chain(adapters: &[u16]) -> Vec<u16> {
     return chain;
 }
 
-fn part1(chain: &[u16]) {
-    let diffs = calculate_differences(chain).unwrap();
-    let answer = diffs.0 * diffs.2;
-    println!("Answer for part 1: {}", answer);
+fn part1(chain: &[u16], model: &AdapterModel) {
+    match calculate_differences(chain, model) {
+        Ok(diffs) => {
+            let answer = diffs[1] as u32 * diffs.get(3).copied().unwrap_or(0) as u32;
+            println!("Answer for part 1: {}", answer);
+            println!("Differences: {:?}", diffs);
+        },
+        Err(ChainError::Gap(..)) => println!("The adapters can't all be chained together"),
+        Err(e) => exit_with(e),
+    }
+}
+
+fn part2(chain: &[u16], model: &AdapterModel) {
+    match count_chains(chain, model) {
+        Ok(chains) => println!("Answer for part 2: {}", chains),
+        Err(e) => exit_with(e),
+    }
+}
+
+fn exit_with(error: ChainError) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("--{}=", name);
+    return args.iter().find_map(|a| a.strip_prefix(&prefix));
+}
+
+fn parse_model(args: &[String]) -> AdapterModel {
+    let mut model = AdapterModel::default();
+    let value = |name: &str| arg_value(args, name);
+
+    if let Some(v) = value("min-step") {
+        model.min_step = v.parse().unwrap();
+    }
+    if let Some(v) = value("max-step") {
+        model.max_step = v.parse().unwrap();
+    }
+    if let Some(v) = value("device-offset") {
+        model.device_offset = v.parse().unwrap();
+    }
+    if let Some(v) = value("exclude") {
+        let excluded: Vec<u16> = v.split(',').map(|a| a.parse().unwrap()).collect();
+        model.rule = Some(Box::new(move |a| !excluded.contains(&a)));
+    }
+    return model;
+}
+
+fn format_chain(chain: &[u16]) -> String {
+    return chain.iter().map(|j| j.to_string()).collect::<Vec<_>>().join(" ");
 }
 
-fn part2(chain: &[u16]) {
-    let chains = count_chains(&chain);
-    println!("Answer for part 2: {}", chains);
+fn print_chains(chain: &[u16], model: &AdapterModel, args: &[String]) {
+    let space = match ChainSpace::new(chain, model) {
+        Ok(space) => space,
+        Err(e) => exit_with(e),
+    };
+    if let Some(n) = arg_value(args, "list") {
+        for chain in space.iter().take(n.parse().unwrap()) {
+            println!("{}", format_chain(&chain));
+        }
+    }
+    if let Some(n) = arg_value(args, "sample") {
+        let seed = arg_value(args, "seed").map_or(0, |s| s.parse().unwrap());
+        for chain in space.sample(seed, n.parse().unwrap()) {
+            println!("{}", format_chain(&chain));
+        }
+    }
+    if args.iter().any(|a| a == "--extremes") {
+        if let Some(chain) = space.extreme(false) {
+            println!("Fewest adapters ({}): {}", chain.len(), format_chain(&chain));
+        }
+        if let Some(chain) = space.extreme(true) {
+            println!("Most adapters ({}): {}", chain.len(), format_chain(&chain));
+        }
+    }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let model = parse_model(&args);
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
     let adapters = parse_adapters(&input);
     let chain = prepare_chain(&adapters);
 
-    part1(&chain);
-    part2(&chain);
+    part1(&chain, &model);
+    part2(&chain, &model);
+
+    if args.iter().any(|a| a == "--table") {
+        print!("{}", format_table(&chain_table(&chain, &model)));
+    }
+    print_chains(&chain, &model, &args);
 }
 
 #[cfg(test)]
@@ -90,8 +464,8 @@ mod tests {
     #[test]
     fn test_calculate_differences() {
         let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
-        let expected = (7, 0, 5);
-        let actual = calculate_differences(&chain).unwrap();
+        let expected = vec![0, 7, 0, 5];
+        let actual = calculate_differences(&chain, &AdapterModel::default()).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -148,15 +522,93 @@ mod tests {
 3";
         let adapters = parse_adapters(&input);
         let chain = prepare_chain(&adapters);
-        let diffs = calculate_differences(&chain).unwrap();
-        assert_eq!((22, 0, 10), diffs);
+        let diffs = calculate_differences(&chain, &AdapterModel::default()).unwrap();
+        assert_eq!(vec![0, 22, 0, 10], diffs);
     }
 
     #[test]
     fn test_count_chains() {
         let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
-        let actual = count_chains(&chain);
-        assert_eq!(actual, 8);
+        let actual = count_chains(&chain, &AdapterModel::default());
+        assert_eq!(actual, Ok(BigUint::from(8u32)));
+    }
+
+    #[test]
+    fn test_chain_table() {
+        let chain = vec![1, 4, 5, 6, 7];
+        let table = chain_table(&chain, &AdapterModel::default());
+        let rows: Vec<_> = table.iter().map(|s| (s.joltage, s.ways.to_string(), s.from.clone())).collect();
+        assert_eq!(rows, vec![
+            (0, String::from("1"), vec![]),
+            (1, String::from("1"), vec![0]),
+            (4, String::from("1"), vec![1]),
+            (5, String::from("1"), vec![2]),
+            (6, String::from("2"), vec![2, 3]),
+            (7, String::from("4"), vec![2, 3, 4]),
+        ]);
+        assert!(format_table(&table).contains("       7                     4  4, 5, 6\n"));
+    }
+
+    #[test]
+    fn test_count_chains_big() {
+        let chain: Vec<u16> = (1..=200).collect();
+        let count = count_chains(&chain, &AdapterModel::default()).unwrap();
+        assert!(count > BigUint::from(u64::MAX));
+        assert_eq!(count.to_string(), "52622583840983769603765180599790256716084480555530641");
+    }
+
+    #[test]
+    fn test_adapter_model_max_step() {
+        let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let model = AdapterModel {max_step: 2, device_offset: 2, ..Default::default()};
+        assert_eq!(calculate_differences(&chain, &model), Err(ChainError::Gap(1, 4)));
+        assert_eq!(count_chains(&chain, &model), Ok(BigUint::from(0u32)));
+
+        let chain = vec![1, 2, 3, 5];
+        assert_eq!(calculate_differences(&chain, &model), Ok(vec![0, 3, 2]));
+        assert_eq!(count_chains(&chain, &model), Ok(BigUint::from(3u32)));
+    }
+
+    #[test]
+    fn test_adapter_model_wide() {
+        let chain = vec![1, 4, 6, 7];
+        let model = AdapterModel {max_step: 5, device_offset: 5, ..Default::default()};
+        assert_eq!(calculate_differences(&chain, &model), Ok(vec![0, 2, 1, 1, 0, 1]));
+        assert_eq!(count_chains(&chain, &model), Ok(BigUint::from(5u32)));
+    }
+
+    #[test]
+    fn test_adapter_model_small_offset() {
+        // The device at 3 jolts can follow the outlet or either adapter
+        let model = AdapterModel {device_offset: 1, ..Default::default()};
+        assert_eq!(count_chains(&[1, 2], &model), Ok(BigUint::from(4u32)));
+        assert_eq!(calculate_differences(&[1, 2], &model), Ok(vec![0, 3, 0, 0]));
+        assert_eq!(count_chains(&[], &model), Ok(BigUint::from(1u32)));
+
+        let model = AdapterModel {device_offset: 2, ..Default::default()};
+        assert_eq!(count_chains(&[1, 2, 3, 4], &model), Ok(BigUint::from(11u32)));
+    }
+
+    #[test]
+    fn test_adapter_model_errors() {
+        let model = AdapterModel {max_step: 0, ..Default::default()};
+        assert_eq!(calculate_differences(&[1, 2], &model), Err(ChainError::ZeroMaxStep));
+        assert_eq!(count_chains(&[1, 2], &model), Err(ChainError::ZeroMaxStep));
+
+        let model = AdapterModel::default();
+        assert_eq!(calculate_differences(&[65532], &model), Err(ChainError::Gap(0, 65532)));
+        assert_eq!(count_chains(&[1, 65534], &model), Err(ChainError::DeviceOverflow(65534)));
+    }
+
+    #[test]
+    fn test_adapter_model_rule() {
+        let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let model = AdapterModel {rule: Some(Box::new(|a| a != 5)), ..Default::default()};
+        assert_eq!(calculate_differences(&chain, &model), Ok(vec![0, 5, 1, 5]));
+        assert_eq!(count_chains(&chain, &model), Ok(BigUint::from(4u32)));
+
+        let model = AdapterModel {min_step: 2, ..Default::default()};
+        assert_eq!(count_chains(&chain, &model), Ok(BigUint::from(0u32)));
     }
 
     #[test]
@@ -194,7 +646,93 @@ mod tests {
 3";
         let adapters = parse_adapters(&input);
         let chain = prepare_chain(&adapters);
-        let actual = count_chains(&chain);
-        assert_eq!(actual, 19208);
+        let actual = count_chains(&chain, &AdapterModel::default());
+        assert_eq!(actual, Ok(BigUint::from(19208u32)));
+    }
+
+    #[test]
+    fn test_chain_space_iter() {
+        let chain = vec![1, 2, 3, 4];
+        let model = AdapterModel::default();
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        let chains: Vec<_> = space.iter().collect();
+        assert_eq!(chains, vec![
+            vec![1, 2, 3, 4],
+            vec![1, 2, 4],
+            vec![1, 3, 4],
+            vec![1, 4],
+            vec![2, 3, 4],
+            vec![2, 4],
+            vec![3, 4],
+        ]);
+        assert_eq!(space.total(), &BigUint::from(7u32));
+        for (i, chain) in chains.iter().enumerate() {
+            assert_eq!(space.nth(&BigUint::from(i)).as_ref(), Some(chain));
+        }
+        assert_eq!(space.nth(&BigUint::from(7u32)), None);
+    }
+
+    #[test]
+    fn test_chain_space_iter_example() {
+        let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let model = AdapterModel::default();
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        let chains: Vec<_> = space.iter().collect();
+        assert_eq!(chains.len(), 8);
+        assert_eq!(chains[0], chain);
+        assert_eq!(chains[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+        let mut sorted = chains.clone();
+        sorted.sort();
+        assert_eq!(sorted, chains);
+    }
+
+    #[test]
+    fn test_chain_space_unreachable() {
+        let chain = vec![1, 5];
+        let model = AdapterModel::default();
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        assert_eq!(space.iter().next(), None);
+        assert_eq!(space.extreme(false), None);
+        assert!(space.sample(1, 3).is_empty());
+    }
+
+    #[test]
+    fn test_chain_space_small_offset() {
+        // The device fits one jolt above the outlet or either adapter, so a
+        // chain can stop anywhere, including straight away
+        let chain = vec![1, 2];
+        let model = AdapterModel {device_offset: 1, ..Default::default()};
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        let all: Vec<_> = space.iter().collect();
+        assert_eq!(all, vec![vec![], vec![1], vec![1, 2], vec![2]]);
+        assert_eq!(space.total(), &count_chains(&chain, &model).unwrap());
+        for (i, chain) in all.iter().enumerate() {
+            assert_eq!(space.nth(&BigUint::from(i)).as_ref(), Some(chain));
+        }
+        assert_eq!(space.extreme(false), Some(vec![]));
+        assert_eq!(space.extreme(true), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_chain_space_sample() {
+        let chain = vec![1, 2, 3, 4];
+        let model = AdapterModel::default();
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        let samples = space.sample(42, 700);
+        assert_eq!(samples, space.sample(42, 700));
+        let all: Vec<_> = space.iter().collect();
+        for chain in &all {
+            let seen = samples.iter().filter(|s| *s == chain).count();
+            assert!(seen > 50 && seen < 150, "{:?} seen {} times", chain, seen);
+        }
+    }
+
+    #[test]
+    fn test_chain_space_extremes() {
+        let chain = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let model = AdapterModel::default();
+        let space = ChainSpace::new(&chain, &model).unwrap();
+        assert_eq!(space.extreme(false), Some(vec![1, 4, 7, 10, 12, 15, 16, 19]));
+        assert_eq!(space.extreme(true), Some(chain));
     }
 }
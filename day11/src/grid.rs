@@ -0,0 +1,377 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::RangeInclusive;
+
+use crate::{Layout, SeatRules};
+
+const WORD_BITS: usize = 64;
+
+/// How a simulation ended
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing changed after this many rounds
+    Stable(usize),
+    /// The seating after round `start` came back `period` rounds later
+    Cycle {start: usize, period: usize},
+    /// Still changing after this many rounds, the limit
+    Unfinished(usize),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Stable(rounds) => write!(f, "stable after {} rounds", rounds),
+            Outcome::Cycle {start, period} => {
+                write!(f, "repeats every {} rounds from round {}", period, start)
+            },
+            Outcome::Unfinished(rounds) => write!(f, "still changing after {} rounds", rounds),
+        }
+    }
+}
+
+/// A seating plan for large layouts. Seats are numbered in row order and
+/// their states kept in a pair of bitsets, one read from while the other is
+/// written to. Each seat's neighbours are worked out once up front, and a
+/// round only looks at seats next to something that changed in the last one.
+#[derive(Clone)]
+pub struct SeatGrid {
+    /// Position in the layout of each seat
+    positions: Vec<(usize, usize)>,
+    /// `neighbours[neighbour_starts[i]..neighbour_starts[i + 1]]` are the
+    /// seats counted for seat `i`
+    neighbour_starts: Vec<usize>,
+    neighbours: Vec<u32>,
+    /// The same again, but the seats that count seat `i`
+    dependent_starts: Vec<usize>,
+    dependents: Vec<u32>,
+    birth: RangeInclusive<usize>,
+    survival: RangeInclusive<usize>,
+    current: Vec<u64>,
+    next: Vec<u64>,
+    /// Seats to look at next round
+    dirty: Vec<u64>,
+}
+
+fn get_bit(bits: &[u64], index: usize) -> bool {
+    return bits[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0;
+}
+
+fn set_bit(bits: &mut [u64], index: usize) {
+    bits[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+}
+
+fn flip_bit(bits: &mut [u64], index: usize) {
+    bits[index / WORD_BITS] ^= 1 << (index % WORD_BITS);
+}
+
+/// Flattens a list of lists into one list plus where each one starts
+fn flatten(lists: Vec<Vec<u32>>) -> (Vec<usize>, Vec<u32>) {
+    let mut starts = Vec::with_capacity(lists.len() + 1);
+    let mut flat = Vec::new();
+    starts.push(0);
+    for list in lists {
+        flat.extend(list);
+        starts.push(flat.len());
+    }
+    return (starts, flat);
+}
+
+impl SeatGrid {
+    pub fn new(layout: &Layout, rules: &SeatRules) -> Self {
+        let mut ids = Vec::with_capacity(layout.len());
+        let mut positions = Vec::new();
+        for (row, seats) in layout.iter().enumerate() {
+            let row_ids: Vec<Option<u32>> = seats.iter().enumerate().map(|(col, seat)| {
+                seat.map(|_| {
+                    positions.push((row, col));
+                    (positions.len() - 1) as u32
+                })
+            }).collect();
+            ids.push(row_ids);
+        }
+
+        let seat_count = positions.len();
+        let mut neighbour_lists = Vec::with_capacity(seat_count);
+        let mut dependent_lists = vec![Vec::new(); seat_count];
+        for (id, &(row, col)) in positions.iter().enumerate() {
+            let neighbours: Vec<u32> = rules.neighbourhood.seats(layout, row, col)
+                .into_iter()
+                .map(|(r, c)| ids[r][c].unwrap())
+                .collect();
+            for &neighbour in &neighbours {
+                dependent_lists[neighbour as usize].push(id as u32);
+            }
+            neighbour_lists.push(neighbours);
+        }
+        let (neighbour_starts, neighbours) = flatten(neighbour_lists);
+        let (dependent_starts, dependents) = flatten(dependent_lists);
+
+        let words = seat_count.div_ceil(WORD_BITS);
+        let mut current = vec![0; words];
+        for (id, &(row, col)) in positions.iter().enumerate() {
+            if layout[row][col] == Some(true) {
+                set_bit(&mut current, id);
+            }
+        }
+        let mut dirty = vec![0; words];
+        for id in 0..seat_count {
+            set_bit(&mut dirty, id);
+        }
+
+        return SeatGrid {
+            positions,
+            neighbour_starts,
+            neighbours,
+            dependent_starts,
+            dependents,
+            birth: rules.birth.clone(),
+            survival: rules.survival.clone(),
+            next: current.clone(),
+            current,
+            dirty,
+        };
+    }
+
+    fn neighbours(&self, id: usize) -> &[u32] {
+        return &self.neighbours[self.neighbour_starts[id]..self.neighbour_starts[id + 1]];
+    }
+
+    /// Seats that change in the next round. Only seats marked dirty since the
+    /// last round are looked at; they stay marked until the round is applied,
+    /// so a run can stop here and pick up again later.
+    pub fn changes(&self) -> Vec<u32> {
+        let mut changes = Vec::new();
+        for word_index in 0..self.dirty.len() {
+            let mut word = self.dirty[word_index];
+            while word != 0 {
+                let id = word_index * WORD_BITS + word.trailing_zeros() as usize;
+                word &= word - 1;
+                if self.should_change(id) {
+                    changes.push(id as u32);
+                }
+            }
+        }
+        return changes;
+    }
+
+    fn should_change(&self, id: usize) -> bool {
+        let count = self.neighbours(id).iter()
+            .filter(|&&n| get_bit(&self.current, n as usize))
+            .count();
+        if get_bit(&self.current, id) {
+            return !self.survival.contains(&count);
+        }
+        return self.birth.contains(&count);
+    }
+
+    /// Flips the seats into the other buffer, swaps the buffers over and
+    /// queues everything that might change as a result in place of the seats
+    /// looked at this round
+    pub fn apply(&mut self, changes: &[u32]) {
+        self.next.copy_from_slice(&self.current);
+        for &id in changes {
+            flip_bit(&mut self.next, id as usize);
+        }
+        mem::swap(&mut self.current, &mut self.next);
+
+        self.dirty.fill(0);
+        for &id in changes {
+            let id = id as usize;
+            set_bit(&mut self.dirty, id);
+            for &dependent in &self.dependents[self.dependent_starts[id]..self.dependent_starts[id + 1]] {
+                set_bit(&mut self.dirty, dependent as usize);
+            }
+        }
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.current.hash(&mut hasher);
+        return hasher.finish();
+    }
+
+    /// Whether the seating is the same again after `period` more rounds.
+    /// Runs on a copy so this one is left where it was, dirty seats included.
+    fn repeats_after(&self, period: usize) -> bool {
+        let mut copy = self.clone();
+        for _ in 0..period {
+            let changes = copy.changes();
+            copy.apply(&changes);
+        }
+        return copy.current == self.current;
+    }
+
+    /// Runs at most `max_rounds` rounds, calling `on_round` with the seating
+    /// before the first round and after each one. Every seating seen is
+    /// hashed so that one coming back round again ends the run.
+    pub fn run<F: FnMut(usize, &SeatGrid)>(&mut self, max_rounds: usize, mut on_round: F) -> Outcome {
+        let mut seen = HashMap::new();
+        let mut rounds = 0;
+        on_round(rounds, self);
+        loop {
+            let changes = self.changes();
+            if changes.is_empty() {
+                return Outcome::Stable(rounds);
+            }
+
+            let hash = self.state_hash();
+            if let Some(&start) = seen.get(&hash) {
+                // Hashes can collide, so check it really does come round again
+                let period = rounds - start;
+                if self.repeats_after(period) {
+                    return Outcome::Cycle {start, period};
+                }
+            }
+            seen.insert(hash, rounds);
+
+            if rounds == max_rounds {
+                return Outcome::Unfinished(rounds);
+            }
+            rounds += 1;
+            self.apply(&changes);
+            on_round(rounds, self);
+        }
+    }
+
+    pub fn count_occupied(&self) -> usize {
+        return self.current.iter().map(|w| w.count_ones() as usize).sum();
+    }
+
+    /// Writes the current seating back over a layout of the same shape
+    pub fn to_layout(&self, layout: &Layout) -> Layout {
+        let mut seating = layout.to_owned();
+        for (id, &(row, col)) in self.positions.iter().enumerate() {
+            seating[row][col] = Some(get_bit(&self.current, id));
+        }
+        return seating;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_layout, run_rounds, Neighbourhood};
+
+    fn get_layout() -> Layout {
+        let input = "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL";
+        return parse_layout(input);
+    }
+
+    fn assert_matches_naive(layout: &Layout, rules: &SeatRules, max_rounds: usize) {
+        let mut grid = SeatGrid::new(layout, rules);
+        let mut frames = Vec::new();
+        let outcome = grid.run(max_rounds, |round, grid| frames.push((round, grid.to_layout(layout))));
+        for (round, frame) in frames {
+            assert_eq!(run_rounds(layout, rules, round).0, frame);
+        }
+        match outcome {
+            Outcome::Stable(rounds) => {
+                assert_eq!(run_rounds(layout, rules, max_rounds), (grid.to_layout(layout), rounds, true));
+            },
+            Outcome::Unfinished(rounds) => {
+                assert_eq!(run_rounds(layout, rules, max_rounds), (grid.to_layout(layout), rounds, false));
+            },
+            Outcome::Cycle {start, period} => {
+                assert!(period > 1);
+                let (first, _, stable) = run_rounds(layout, rules, start);
+                assert!(!stable);
+                assert_eq!(run_rounds(layout, rules, start + period).0, first);
+                for earlier in 1..period {
+                    assert_ne!(run_rounds(layout, rules, start + earlier).0, first);
+                }
+                if start > 0 {
+                    let before = run_rounds(layout, rules, start - 1).0;
+                    assert_ne!(run_rounds(layout, rules, start - 1 + period).0, before);
+                }
+            },
+        }
+    }
+
+    #[test]
+    fn test_part_presets() {
+        let layout = get_layout();
+        let mut grid = SeatGrid::new(&layout, &SeatRules::part1());
+        assert_eq!(grid.run(usize::MAX, |_, _| ()), Outcome::Stable(5));
+        assert_eq!(grid.count_occupied(), 37);
+        let mut grid = SeatGrid::new(&layout, &SeatRules::part2());
+        assert_eq!(grid.run(usize::MAX, |_, _| ()), Outcome::Stable(6));
+        assert_eq!(grid.count_occupied(), 26);
+    }
+
+    #[test]
+    fn test_matches_naive() {
+        let layout = get_layout();
+        let custom = [
+            (Neighbourhood::Radius(2), 0..=1, 0..=6),
+            (Neighbourhood::Offsets(vec![(-1, 0), (0, 2), (2, 1)]), 0..=0, 0..=1),
+            (Neighbourhood::LineOfSight, 1..=2, 2..=3),
+        ];
+        for (neighbourhood, birth, survival) in custom.iter().cloned() {
+            let rules = SeatRules {neighbourhood, birth, survival};
+            for max_rounds in 0..12 {
+                assert_matches_naive(&layout, &rules, max_rounds);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle() {
+        let layout = parse_layout("LLLLL");
+        let rules = SeatRules {neighbourhood: Neighbourhood::Adjacent, birth: 0..=0, survival: 0..=0};
+        let mut grid = SeatGrid::new(&layout, &rules);
+        let mut occupied = Vec::new();
+        let outcome = grid.run(usize::MAX, |_, grid| occupied.push(grid.count_occupied()));
+        assert_eq!(outcome, Outcome::Cycle {start: 0, period: 2});
+        assert_eq!(occupied, vec![0, 5, 0]);
+
+        let layout = parse_layout("L##");
+        let mut grid = SeatGrid::new(&layout, &rules);
+        assert_eq!(grid.run(usize::MAX, |_, _| ()), Outcome::Cycle {start: 1, period: 2});
+
+        let mut grid = SeatGrid::new(&layout, &rules);
+        assert_eq!(grid.run(1, |_, _| ()), Outcome::Unfinished(1));
+    }
+
+    #[test]
+    fn test_resume() {
+        let layout = parse_layout("LLLLL");
+        let rules = SeatRules {neighbourhood: Neighbourhood::Adjacent, birth: 0..=0, survival: 0..=0};
+        let mut grid = SeatGrid::new(&layout, &rules);
+        assert_eq!(grid.run(0, |_, _| ()), Outcome::Unfinished(0));
+        assert_eq!(grid.run(10, |_, _| ()), Outcome::Cycle {start: 0, period: 2});
+
+        // Stopping partway through a run that settles still ends up settled
+        let layout = get_layout();
+        let mut grid = SeatGrid::new(&layout, &SeatRules::part1());
+        assert_eq!(grid.run(2, |_, _| ()), Outcome::Unfinished(2));
+        assert_eq!(grid.run(usize::MAX, |_, _| ()), Outcome::Stable(3));
+        assert_eq!(grid.count_occupied(), 37);
+    }
+
+    #[test]
+    fn test_repeats_after() {
+        let layout = parse_layout("LLLLL");
+        let rules = SeatRules {neighbourhood: Neighbourhood::Adjacent, birth: 0..=0, survival: 0..=0};
+        let grid = SeatGrid::new(&layout, &rules);
+        let _ = grid.changes();
+        assert!(!grid.repeats_after(1));
+        assert!(grid.repeats_after(2));
+
+        let mut grid = SeatGrid::new(&get_layout(), &SeatRules::part1());
+        let changes = grid.changes();
+        grid.apply(&changes);
+        assert!(!grid.repeats_after(1));
+    }
+}
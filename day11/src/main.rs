@@ -1,7 +1,16 @@
 use std::cmp;
-use std::io::{self, Read};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::process;
+
+use grid::{Outcome, SeatGrid};
+
+mod grid;
+
+const MAX_CUSTOM_ROUNDS: usize = 1000;
 
-type CountFn = fn(&Layout, usize, usize) -> u8;
 type Layout = Vec<Vec<Option<Seat>>>;
 type Range = (usize, usize);
 type Seat = bool;
@@ -123,32 +132,155 @@ impl Iterator for PosIter {
     }
 }
 
-fn calculate_seating(layout: &Layout, f: CountFn, allowance: u8) -> Layout {
+/// Which seats count as neighbours of a seat
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Neighbourhood {
+    /// The eight surrounding cells
+    Adjacent,
+    /// The first seat visible in each of the eight directions
+    LineOfSight,
+    /// Every cell within this many rows and columns
+    Radius(usize),
+    /// Cells at these (row, column) offsets
+    Offsets(Vec<(isize, isize)>),
+}
+
+impl Neighbourhood {
+    fn count(&self, layout: &Layout, row: usize, col: usize) -> usize {
+        return match self {
+            Neighbourhood::Adjacent => count_adjacent(layout, row, col),
+            Neighbourhood::LineOfSight => count_nearest(layout, row, col),
+            Neighbourhood::Radius(radius) => count_radius(layout, row, col, *radius),
+            Neighbourhood::Offsets(offsets) => count_offsets(layout, row, col, offsets),
+        };
+    }
+
+    /// The seats counted as neighbours of a seat, ignoring whether they're
+    /// occupied
+    fn seats(&self, layout: &Layout, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let in_bounds = |r: isize, c: isize| {
+            r >= 0 && c >= 0 && (r as usize) < layout.len() && (c as usize) < layout[r as usize].len()
+        };
+        let is_seat = |&(r, c): &(usize, usize)| layout[r][c].is_some();
+        return match self {
+            Neighbourhood::Adjacent => Neighbourhood::Radius(1).seats(layout, row, col),
+            Neighbourhood::LineOfSight => {
+                let height = layout.len();
+                let width = layout[0].len();
+                DIRECTIONS.iter().filter_map(|&dir| {
+                    PosIter {row, col, width, height, dir}.find(is_seat)
+                }).collect()
+            },
+            Neighbourhood::Radius(radius) => {
+                let ((min_row, max_row), (min_col, max_col)) =
+                    get_radius_ranges(layout, row, col, *radius);
+                (min_row..max_row)
+                    .flat_map(|r| (min_col..max_col).map(move |c| (r, c)))
+                    .filter(|&pos| pos != (row, col) && is_seat(&pos))
+                    .collect()
+            },
+            Neighbourhood::Offsets(offsets) => offsets.iter()
+                .map(|&(dr, dc)| (row as isize + dr, col as isize + dc))
+                .filter(|&(r, c)| in_bounds(r, c))
+                .map(|(r, c)| (r as usize, c as usize))
+                .filter(is_seat)
+                .collect(),
+        };
+    }
+
+    fn from_str(neighbourhood_str: &str) -> Self {
+        let (kind, arg) = match neighbourhood_str.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (neighbourhood_str, None),
+        };
+        match (kind, arg) {
+            ("adjacent", None) => Neighbourhood::Adjacent,
+            ("sight", None) => Neighbourhood::LineOfSight,
+            ("radius", Some(radius)) => Neighbourhood::Radius(radius.parse().unwrap()),
+            ("offsets", Some(offsets)) => Neighbourhood::Offsets(offsets.split(';').map(|o| {
+                let (row, col) = o.split_once(',').unwrap();
+                (row.trim().parse().unwrap(), col.trim().parse().unwrap())
+            }).collect()),
+            _ => panic!("Unknown neighbourhood: {}", neighbourhood_str),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SeatRules {
+    neighbourhood: Neighbourhood,
+    /// Neighbour counts at which an empty seat becomes occupied
+    birth: RangeInclusive<usize>,
+    /// Neighbour counts at which an occupied seat stays occupied
+    survival: RangeInclusive<usize>,
+}
+
+impl SeatRules {
+    fn part1() -> Self {
+        return SeatRules {
+            neighbourhood: Neighbourhood::Adjacent,
+            birth: 0..=0,
+            survival: 0..=3,
+        };
+    }
+
+    fn part2() -> Self {
+        return SeatRules {
+            neighbourhood: Neighbourhood::LineOfSight,
+            birth: 0..=0,
+            survival: 0..=4,
+        };
+    }
+}
+
+fn parse_count_range(range_str: &str) -> RangeInclusive<usize> {
+    return match range_str.split_once('-') {
+        Some((min, max)) => min.parse().unwrap()..=max.parse().unwrap(),
+        None => {
+            let n = range_str.parse().unwrap();
+            n..=n
+        },
+    };
+}
+
+/// Runs rounds until nothing changes, returning the final seating and how
+/// many rounds changed something
+fn calculate_seating(layout: &Layout, rules: &SeatRules) -> (Layout, usize) {
+    let (seating, rounds, _) = run_rounds(layout, rules, usize::MAX);
+    return (seating, rounds);
+}
+
+/// Runs at most `max_rounds` rounds, also returning whether the seating
+/// settled. Custom rules aren't guaranteed to ever settle.
+fn run_rounds(layout: &Layout, rules: &SeatRules, max_rounds: usize) -> (Layout, usize, bool) {
     let mut seating = layout.to_owned();
+    let mut rounds = 0;
 
     loop {
-        let changes = changes_for_round(&seating, f, allowance);
+        let changes = changes_for_round(&seating, rules);
         if changes.is_empty() {
-            break;
+            return (seating, rounds, true);
         }
+        if rounds == max_rounds {
+            return (seating, rounds, false);
+        }
+        rounds += 1;
 
         for (row, col) in changes {
             let current = seating[row][col].unwrap();
             seating[row][col] = Some(!current);
         }
     }
-
-    return seating;
 }
 
-fn changes_for_round(layout: &Layout, f: CountFn, allowance: u8) -> Vec<(usize, usize)> {
+fn changes_for_round(layout: &Layout, rules: &SeatRules) -> Vec<(usize, usize)> {
     let mut changes = Vec::new();
 
     let rows = layout.len();
     let cols = layout[0].len();
     for row in 0..rows {
         for col in 0..cols {
-            if seat_should_change(layout, row, col, f, allowance) {
+            if seat_should_change(layout, row, col, rules) {
                 changes.push((row, col));
             }
         }
@@ -157,8 +289,17 @@ fn changes_for_round(layout: &Layout, f: CountFn, allowance: u8) -> Vec<(usize,
     return changes;
 }
 
-fn count_adjacent(layout: &Layout, row: usize, col: usize) -> u8 {
-    let ((min_row, max_row), (min_col, max_col)) = get_ranges(layout, row, col);
+fn count_adjacent(layout: &Layout, row: usize, col: usize) -> usize {
+    return count_in_ranges(layout, row, col, get_ranges(layout, row, col));
+}
+
+fn count_radius(layout: &Layout, row: usize, col: usize, radius: usize) -> usize {
+    return count_in_ranges(layout, row, col, get_radius_ranges(layout, row, col, radius));
+}
+
+/// Occupied seats within the ranges, not counting the seat itself
+fn count_in_ranges(layout: &Layout, row: usize, col: usize, ranges: (Range, Range)) -> usize {
+    let ((min_row, max_row), (min_col, max_col)) = ranges;
     let mut count = 0;
     for row in &layout[min_row..max_row] {
         for seat in &row[min_col..max_col] {
@@ -173,7 +314,16 @@ fn count_adjacent(layout: &Layout, row: usize, col: usize) -> u8 {
     return count;
 }
 
-fn count_nearest(layout: &Layout, row: usize, col: usize) -> u8 {
+fn count_offsets(layout: &Layout, row: usize, col: usize, offsets: &[(isize, isize)]) -> usize {
+    return offsets.iter().filter(|&&(dr, dc)| {
+        let r = row as isize + dr;
+        let c = col as isize + dc;
+        r >= 0 && c >= 0 && (r as usize) < layout.len() && (c as usize) < layout[r as usize].len()
+            && layout[r as usize][c as usize] == Some(true)
+    }).count();
+}
+
+fn count_nearest(layout: &Layout, row: usize, col: usize) -> usize {
     let mut count = 0;
     let height = layout.len();
     let width = layout[0].len();
@@ -202,16 +352,14 @@ fn count_nearest(layout: &Layout, row: usize, col: usize) -> u8 {
 }
 
 fn get_ranges(layout: &Layout, row: usize, col: usize) -> (Range, Range) {
-    let min_row = match row.checked_sub(1) {
-        Some(v) => v,
-        None => 0,
-    };
-    let max_row = cmp::min(row + 2, layout.len());
-    let min_col = match col.checked_sub(1) {
-        Some(v) => v,
-        None => 0,
-    };
-    let max_col = cmp::min(col + 2, layout[row].len());
+    return get_radius_ranges(layout, row, col, 1);
+}
+
+fn get_radius_ranges(layout: &Layout, row: usize, col: usize, radius: usize) -> (Range, Range) {
+    let min_row = row.saturating_sub(radius);
+    let max_row = cmp::min(row + radius + 1, layout.len());
+    let min_col = col.saturating_sub(radius);
+    let max_col = cmp::min(col + radius + 1, layout[row].len());
     return ((min_row, max_row), (min_col, max_col));
 }
 
@@ -233,40 +381,127 @@ fn parse_row(line: &str) -> Vec<Option<Seat>> {
     return row;
 }
 
-fn seat_should_change(layout: &Layout, row: usize, col: usize, f: CountFn, allowance: u8) -> bool {
-    let adjacent = f(layout, row, col);
+fn format_layout(layout: &Layout) -> String {
+    let mut output = String::new();
+    for row in layout {
+        output.extend(row.iter().map(|seat| match seat {
+            None => '.',
+            Some(false) => 'L',
+            Some(true) => '#',
+        }));
+        output.push('\n');
+    }
+    return output;
+}
+
+fn seat_should_change(layout: &Layout, row: usize, col: usize, rules: &SeatRules) -> bool {
     match layout[row][col] {
-        Some(false) => adjacent == 0,
-        Some(true) => adjacent >= allowance,
+        Some(false) => rules.birth.contains(&rules.neighbourhood.count(layout, row, col)),
+        Some(true) => !rules.survival.contains(&rules.neighbourhood.count(layout, row, col)),
         None => false,
     }
 }
 
-fn part1(layout: &Layout) {
-    let seating = calculate_seating(layout, count_adjacent, 4);
-    let occupied: usize = seating
+fn count_occupied(layout: &Layout) -> usize {
+    return layout
         .iter()
         .map(|r| r.iter().filter(|s| s.unwrap_or_default()).count())
         .sum();
-    println!("Seats occupied in part 1: {}", occupied);
 }
 
-fn part2(layout: &Layout) {
-    let seating = calculate_seating(layout, count_nearest, 5);
-    let occupied: usize = seating
-        .iter()
-        .map(|r| r.iter().filter(|s| s.unwrap_or_default()).count())
-        .sum();
-    println!("Seats occupied in part 2: {}", occupied);
+/// Settles the seating with either the bitset grid or the straightforward
+/// simulation, returning the occupied seats and how it ended. Only the grid
+/// notices seatings that cycle; the straightforward one never stops for them.
+fn settle(layout: &Layout, rules: &SeatRules, naive: bool) -> (usize, Outcome) {
+    if naive {
+        let (seating, rounds) = calculate_seating(layout, rules);
+        return (count_occupied(&seating), Outcome::Stable(rounds));
+    }
+    let mut grid = SeatGrid::new(layout, rules);
+    let outcome = grid.run(usize::MAX, |_, _| ());
+    return (grid.count_occupied(), outcome);
+}
+
+fn part1(layout: &Layout, naive: bool) {
+    let (occupied, outcome) = settle(layout, &SeatRules::part1(), naive);
+    println!("Seats occupied in part 1: {} ({})", occupied, outcome);
+}
+
+fn part2(layout: &Layout, naive: bool) {
+    let (occupied, outcome) = settle(layout, &SeatRules::part2(), naive);
+    println!("Seats occupied in part 2: {} ({})", occupied, outcome);
+}
+
+fn write_frame(out: &mut dyn Write, round: usize, layout: &Layout) -> io::Result<()> {
+    writeln!(out, "Round {}:", round)?;
+    write!(out, "{}", format_layout(layout))?;
+    return writeln!(out);
+}
+
+fn parse_rules(args: &[String]) -> Option<SeatRules> {
+    let value = |name: &str| {
+        let prefix = format!("--{}=", name);
+        args.iter().find_map(|a| a.strip_prefix(&prefix))
+    };
+    let neighbourhood = value("neighbourhood")?;
+    return Some(SeatRules {
+        neighbourhood: Neighbourhood::from_str(neighbourhood),
+        birth: parse_count_range(value("birth").unwrap_or("0")),
+        survival: parse_count_range(value("survival").unwrap_or("0-3")),
+    });
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
     let layout = parse_layout(&input);
 
-    part1(&layout);
-    part2(&layout);
+    let naive = args.iter().any(|a| a == "--naive");
+    let rules = parse_rules(&args);
+    let wants_frames = args.iter().any(|a| a == "--frames" || a.starts_with("--frames="));
+    if wants_frames && (naive || rules.is_none()) {
+        // Only the grid runs round by round where frames can be written, and
+        // the part 1 and 2 presets are never drawn
+        eprintln!("--frames needs custom rules and can't be used with --naive");
+        process::exit(1);
+    }
+    part1(&layout, naive);
+    part2(&layout, naive);
+
+    if let Some(rules) = rules {
+        let max_rounds = args.iter()
+            .find_map(|a| a.strip_prefix("--max-rounds=").map(|n| n.parse().unwrap()))
+            .unwrap_or(MAX_CUSTOM_ROUNDS);
+        let (seating, outcome) = if naive {
+            let (seating, rounds, stable) = run_rounds(&layout, &rules, max_rounds);
+            let outcome = if stable { Outcome::Stable(rounds) } else { Outcome::Unfinished(rounds) };
+            (seating, outcome)
+        } else {
+            // `--frames` writes every round to stdout, `--frames=FILE` to a file
+            let mut frames: Option<Box<dyn Write>> = if args.iter().any(|a| a == "--frames") {
+                Some(Box::new(io::stdout()))
+            } else {
+                args.iter().find_map(|a| a.strip_prefix("--frames=")).map(|path| {
+                    Box::new(BufWriter::new(File::create(path).unwrap())) as Box<dyn Write>
+                })
+            };
+            let mut grid = SeatGrid::new(&layout, &rules);
+            let outcome = grid.run(max_rounds, |round, grid| {
+                if let Some(out) = frames.as_mut() {
+                    write_frame(out, round, &grid.to_layout(&layout)).unwrap();
+                }
+            });
+            if let Some(mut out) = frames {
+                out.flush().unwrap();
+            }
+            (grid.to_layout(&layout), outcome)
+        };
+        println!("Seats occupied with custom rules: {} ({})", count_occupied(&seating), outcome);
+        if args.iter().any(|a| a == "--show") {
+            print!("{}", format_layout(&seating));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -363,8 +598,8 @@ L.L.L..L..
 #.######.#
 #.#####.##";
         let layout = parse_layout(input);
-        assert!(seat_should_change(&layout, 1, 1, count_adjacent, 4));
-        assert!(!seat_should_change(&layout, 1, 0, count_adjacent, 4));
+        assert!(seat_should_change(&layout, 1, 1, &SeatRules::part1()));
+        assert!(!seat_should_change(&layout, 1, 0, &SeatRules::part1()));
     }
 
     #[test]
@@ -380,7 +615,7 @@ L.L.L..L..
 #.LLLLLL.L
 #.#LLLL.##";
         let layout = parse_layout(input);
-        assert!(seat_should_change(&layout, 1, 2, count_adjacent, 4));
+        assert!(seat_should_change(&layout, 1, 2, &SeatRules::part1()));
     }
 
     #[test]
@@ -397,7 +632,7 @@ L.L.L..#..
 #.#L#L#.##";
         let layout = parse_layout(input);
         let expected = vec![(2, 2), (3, 2), (3, 3), (4, 2), (5, 2), (7, 4), (7, 5)];
-        let actual = changes_for_round(&layout, count_adjacent, 4);
+        let actual = changes_for_round(&layout, &SeatRules::part1());
         assert_eq!(actual, expected);
     }
 
@@ -414,7 +649,7 @@ LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
         let layout = parse_layout(input);
-        let seating = calculate_seating(&layout, count_adjacent, 4);
+        let (seating, rounds) = calculate_seating(&layout, &SeatRules::part1());
         let expected = vec![
             Some(false),
             None,
@@ -428,5 +663,54 @@ L.LLLLL.LL";
             None,
         ];
         assert_eq!(seating[2], expected);
+        assert_eq!(rounds, 5);
+        assert_eq!(count_occupied(&seating), 37);
+
+        let (seating, rounds) = calculate_seating(&layout, &SeatRules::part2());
+        assert_eq!(rounds, 6);
+        assert_eq!(count_occupied(&seating), 26);
+    }
+
+    #[test]
+    fn test_neighbourhoods() {
+        let layout = get_layout();
+        assert_eq!(Neighbourhood::Radius(1).count(&layout, 5, 5), 1);
+        assert_eq!(Neighbourhood::Radius(2).count(&layout, 5, 5), 1);
+        assert_eq!(Neighbourhood::Radius(3).count(&layout, 5, 5), 2);
+        let knight = Neighbourhood::from_str("offsets:-2,-1;-2,1;2,-1;2,1;-1,-2;-1,2;1,-2;1,2");
+        assert_eq!(knight.count(&layout, 1, 7), 1);
+        assert_eq!(knight.count(&layout, 0, 0), 0);
+        assert_eq!(Neighbourhood::from_str("sight"), Neighbourhood::LineOfSight);
+    }
+
+    #[test]
+    fn test_run_rounds_limit() {
+        let layout = get_layout();
+        let (_, rounds, stable) = run_rounds(&layout, &SeatRules::part1(), 2);
+        assert_eq!((rounds, stable), (2, false));
+        let (_, rounds, stable) = run_rounds(&layout, &SeatRules::part1(), 3);
+        assert_eq!((rounds, stable), (3, true));
+    }
+
+    #[test]
+    fn test_format_layout() {
+        let input = "#.LL.L#.##
+#LLLLLL.L#
+";
+        assert_eq!(format_layout(&parse_layout(input)), input);
+    }
+
+    #[test]
+    fn test_write_frame() {
+        let layout = parse_layout("#.L\nLL#");
+        let mut out = Vec::new();
+        write_frame(&mut out, 3, &layout).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Round 3:\n#.L\nLL#\n\n");
+    }
+
+    #[test]
+    fn test_parse_count_range() {
+        assert_eq!(parse_count_range("2"), 2..=2);
+        assert_eq!(parse_count_range("0-4"), 0..=4);
     }
 }
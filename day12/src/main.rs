@@ -1,5 +1,11 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
 use std::io::{self, Read};
+use std::ops::Neg;
+use std::process;
 
+type PrecisePos = (f64, f64);
 type ShipPos = (i32, i32);
 type WaypointPos = (i32, i32);
 
@@ -14,12 +20,99 @@ enum Move {
     Forward(u32),
 }
 
-fn add_degrees(first: u16, second: u16) -> u16 {
-    let mut degrees = first + second;
-    if degrees >= 360 {
-        degrees -= 360;
+impl Move {
+    /// Whether the move keeps the ship on a compass point, which is all the
+    /// integer navigation can handle
+    fn is_right_angle(&self) -> bool {
+        return match self {
+            Move::Left(v) | Move::Right(v) => v % 90 == 0,
+            _ => true,
+        };
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::North(v) => write!(f, "N{}", v),
+            Move::South(v) => write!(f, "S{}", v),
+            Move::East(v) => write!(f, "E{}", v),
+            Move::West(v) => write!(f, "W{}", v),
+            Move::Left(v) => write!(f, "L{}", v),
+            Move::Right(v) => write!(f, "R{}", v),
+            Move::Forward(v) => write!(f, "F{}", v),
+        }
+    }
+}
+
+/// Every position the ship was in, starting where it set off, and where the
+/// waypoint was each time when it's following one
+#[derive(Debug, PartialEq, Eq)]
+struct Route {
+    ships: Vec<ShipPos>,
+    waypoints: Option<Vec<WaypointPos>>,
+}
+
+impl Route {
+    /// Furthest the ship got from the origin, by Manhattan distance
+    fn max_distance(&self) -> u64 {
+        return self.ships.iter().map(|&(ew, ns)| manhattan_distance(ew, ns)).max().unwrap_or(0);
+    }
+
+    /// Manhattan distance covered by every move added up
+    fn total_distance(&self) -> u64 {
+        return self.ships.windows(2).map(|pair| {
+            let (from, to) = (pair[0], pair[1]);
+            (to.0 as i64 - from.0 as i64).unsigned_abs() + (to.1 as i64 - from.1 as i64).unsigned_abs()
+        }).sum();
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("step,ship_ew,ship_ns");
+        if self.waypoints.is_some() {
+            csv.push_str(",waypoint_ew,waypoint_ns");
+        }
+        csv.push('\n');
+        for (step, ship) in self.ships.iter().enumerate() {
+            csv.push_str(&format!("{},{},{}", step, ship.0, ship.1));
+            if let Some(waypoints) = &self.waypoints {
+                csv.push_str(&format!(",{},{}", waypoints[step].0, waypoints[step].1));
+            }
+            csv.push('\n');
+        }
+        return csv;
+    }
+
+    /// The ship's route as an SVG polyline, with north at the top
+    fn to_svg(&self) -> String {
+        let min_ew = self.ships.iter().map(|s| s.0).min().unwrap_or(0);
+        let max_ew = self.ships.iter().map(|s| s.0).max().unwrap_or(0);
+        let min_ns = self.ships.iter().map(|s| s.1).min().unwrap_or(0);
+        let max_ns = self.ships.iter().map(|s| s.1).max().unwrap_or(0);
+        let points: Vec<String> = self.ships.iter()
+            .map(|&(ew, ns)| format!("{},{}", ew, -(ns as i64)))
+            .collect();
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_ew as i64 - 1,
+            -(max_ns as i64) - 1,
+            max_ew as i64 - min_ew as i64 + 2,
+            max_ns as i64 - min_ns as i64 + 2,
+        );
+        svg.push_str(&format!(
+            "  <polyline fill=\"none\" stroke=\"black\" stroke-width=\"1\" \
+             vector-effect=\"non-scaling-stroke\" points=\"{}\"/>\n",
+            points.join(" "),
+        ));
+        svg.push_str("</svg>\n");
+        return svg;
     }
-    return degrees;
+}
+
+fn add_degrees(first: u16, second: u16) -> u16 {
+    let degrees = (first as u32 + second as u32) % 360;
+    return degrees as u16;
 }
 
 fn calculate_position(ew: i32, ns: i32, dir: u16, mv: Move) -> (i32, i32, u16) {
@@ -35,7 +128,25 @@ fn calculate_position(ew: i32, ns: i32, dir: u16, mv: Move) -> (i32, i32, u16) {
             90 => (ew + (v as i32), ns, dir),
             180 => (ew, ns - (v as i32), dir),
             270 => (ew - (v as i32), ns, dir),
-            _ => panic!("Unexpected direction: {}", dir),
+            _ => panic!("Unexpected direction: {} (use precise navigation for other angles)", dir),
+        },
+    }
+}
+
+/// Like `calculate_position`, but for any heading, in degrees clockwise
+/// from north
+fn calculate_precise_position(pos: PrecisePos, heading: f64, mv: Move) -> (PrecisePos, f64) {
+    let (ew, ns) = pos;
+    match mv {
+        Move::North(v) => ((ew, ns + v as f64), heading),
+        Move::South(v) => ((ew, ns - v as f64), heading),
+        Move::East(v) => ((ew + v as f64, ns), heading),
+        Move::West(v) => ((ew - v as f64, ns), heading),
+        Move::Left(v) => (pos, (heading - v as f64).rem_euclid(360.0)),
+        Move::Right(v) => (pos, (heading + v as f64).rem_euclid(360.0)),
+        Move::Forward(v) => {
+            let (sin, cos) = sin_cos_degrees(heading);
+            ((ew + sin * v as f64, ns + cos * v as f64), heading)
         },
     }
 }
@@ -50,8 +161,8 @@ fn calculate_waypoint_positions(
         Move::South(v) => (ship, (waypoint.0, waypoint.1 - v as i32)),
         Move::East(v) => (ship, (waypoint.0 + v as i32, waypoint.1)),
         Move::West(v) => (ship, (waypoint.0 - v as i32, waypoint.1)),
-        Move::Left(v) => (ship, rotate_waypoint(waypoint, -(v as i16))),
-        Move::Right(v) => (ship, rotate_waypoint(waypoint, v as i16)),
+        Move::Left(v) => (ship, rotate_waypoint(waypoint, -(v as i32))),
+        Move::Right(v) => (ship, rotate_waypoint(waypoint, v as i32)),
         Move::Forward(v) => {
             let ship_ew = ship.0 + (waypoint.0 * v as i32);
             let ship_ns = ship.1 + (waypoint.1 * v as i32);
@@ -60,8 +171,127 @@ fn calculate_waypoint_positions(
     }
 }
 
-fn manhattan_distance(ew: i32, ns: i32) -> u32 {
-    let distance = (ew.abs() + ns.abs()) as u32;
+/// Like `calculate_waypoint_positions`, but the waypoint can be turned by any
+/// angle
+fn calculate_precise_waypoint_positions(
+    ship: PrecisePos,
+    waypoint: PrecisePos,
+    mv: Move,
+) -> (PrecisePos, PrecisePos) {
+    match mv {
+        Move::North(v) => (ship, (waypoint.0, waypoint.1 + v as f64)),
+        Move::South(v) => (ship, (waypoint.0, waypoint.1 - v as f64)),
+        Move::East(v) => (ship, (waypoint.0 + v as f64, waypoint.1)),
+        Move::West(v) => (ship, (waypoint.0 - v as f64, waypoint.1)),
+        Move::Left(v) => (ship, rotate_precise_waypoint(waypoint, -(v as f64))),
+        Move::Right(v) => (ship, rotate_precise_waypoint(waypoint, v as f64)),
+        Move::Forward(v) => {
+            let ship_ew = ship.0 + (waypoint.0 * v as f64);
+            let ship_ns = ship.1 + (waypoint.1 * v as f64);
+            ((ship_ew, ship_ns), waypoint)
+        }
+    }
+}
+
+/// An equivalent list of moves for the ship on its own, from any starting
+/// point and compass heading. Moves north, south, east and west don't care
+/// which way the ship faces, so they're added up into one or two moves, and
+/// so are forward moves by which way they went compared to the start.
+fn compress_moves(moves: &[Move]) -> Vec<Move> {
+    let (mut ew, mut ns) = (0, 0);
+    let mut heading = 0;
+    // How far forward the ship went while facing each way, clockwise from
+    // the way it started
+    let mut forward = [0i64; 4];
+    for mv in moves {
+        if !mv.is_right_angle() {
+            panic!("Unexpected move: {} (only right angles can be compressed)", mv);
+        }
+        match *mv {
+            Move::North(v) => ns += v as i64,
+            Move::South(v) => ns -= v as i64,
+            Move::East(v) => ew += v as i64,
+            Move::West(v) => ew -= v as i64,
+            Move::Left(v) => heading = sub_degrees(heading, v),
+            Move::Right(v) => heading = add_degrees(heading, v),
+            Move::Forward(v) => forward[(heading / 90) as usize] += v as i64,
+        }
+    }
+
+    let mut compressed = Vec::new();
+    push_translation(&mut compressed, ew, ns);
+    let legs: Vec<(u16, i64)> = [(0, forward[0] - forward[2]), (90, forward[1] - forward[3])]
+        .iter()
+        .filter(|&&(_, distance)| distance != 0)
+        .map(|&(dir, distance)| if distance < 0 { (dir + 180, -distance) } else { (dir, distance) })
+        .collect();
+    // Going the other way round can save a turn
+    let orders = [legs.clone(), legs.into_iter().rev().collect()];
+    let best = orders.iter().map(|legs| {
+        let mut route = Vec::new();
+        let mut dir = 0;
+        for &(leg_dir, distance) in legs {
+            route.extend(turn_between(dir, leg_dir));
+            push_long_move(&mut route, distance.unsigned_abs(), Move::Forward);
+            dir = leg_dir;
+        }
+        route.extend(turn_between(dir, heading));
+        route
+    }).min_by_key(|route| route.len()).unwrap();
+    compressed.extend(best);
+
+    if compressed.len() < moves.len() {
+        return compressed;
+    }
+    return moves.to_vec();
+}
+
+/// An equivalent list of moves when following the waypoint, from any
+/// starting point and waypoint. Between two forward moves, turns and moves of
+/// the waypoint become at most one turn then one move each way. Forward moves
+/// next to each other are added up, and ones either side of a half turn
+/// cancel out.
+fn compress_waypoint_moves(moves: &[Move]) -> Vec<Move> {
+    let mut compressed = Vec::new();
+    let mut turn = 0;
+    // Kept wider than a waypoint so that long moves add up without wrapping
+    let mut shift: (i64, i64) = (0, 0);
+    for mv in moves {
+        if !mv.is_right_angle() {
+            panic!("Unexpected move: {} (only right angles can be compressed)", mv);
+        }
+        match *mv {
+            Move::North(v) => shift.1 += v as i64,
+            Move::South(v) => shift.1 -= v as i64,
+            Move::East(v) => shift.0 += v as i64,
+            Move::West(v) => shift.0 -= v as i64,
+            Move::Left(v) => {
+                turn = sub_degrees(turn, v);
+                shift = rotate_waypoint(shift, -(v as i32));
+            },
+            Move::Right(v) => {
+                turn = add_degrees(turn, v);
+                shift = rotate_waypoint(shift, v as i32);
+            },
+            Move::Forward(v) => {
+                push_waypoint_block(&mut compressed, turn, shift);
+                push_waypoint_move(&mut compressed, Move::Forward(v));
+                turn = 0;
+                shift = (0, 0);
+            },
+        }
+    }
+    push_waypoint_block(&mut compressed, turn, shift);
+
+    if compressed.len() < moves.len() {
+        return compressed;
+    }
+    return moves.to_vec();
+}
+
+fn manhattan_distance(ew: i32, ns: i32) -> u64 {
+    // Two i32 coordinates can add up to more than an i32 or u32 holds
+    let distance = (ew as i64).unsigned_abs() + (ns as i64).unsigned_abs();
     return distance;
 }
 
@@ -77,8 +307,10 @@ fn parse_move(move_str: &str) -> Move {
         'S' => Move::South(value),
         'E' => Move::East(value),
         'W' => Move::West(value),
-        'L' => Move::Left(value as u16),
-        'R' => Move::Right(value as u16),
+        // Turns are taken modulo 360 before narrowing, so any size of turn
+        // keeps its heading
+        'L' => Move::Left((value % 360) as u16),
+        'R' => Move::Right((value % 360) as u16),
         'F' => Move::Forward(value),
         _ => panic!("Unexpected action: {}", action),
     }
@@ -89,13 +321,11 @@ fn parse_moves(input: &str) -> Vec<Move> {
 }
 
 fn perform_moves(moves: &[Move], ew: i32, ns: i32, dir: u16) -> (i32, i32, u16) {
-    match moves.first() {
-        Some(mv) => {
-            let (new_ew, new_ns, new_dir) = calculate_position(ew, ns, dir, *mv);
-            return perform_moves(&moves[1..], new_ew, new_ns, new_dir);
-        }
-        None => return (ew, ns, dir),
+    let mut state = (ew, ns, dir);
+    for mv in moves {
+        state = calculate_position(state.0, state.1, state.2, *mv);
     }
+    return state;
 }
 
 fn perform_waypoint_moves(
@@ -103,24 +333,146 @@ fn perform_waypoint_moves(
     ship: ShipPos,
     waypoint: WaypointPos,
 ) -> (ShipPos, WaypointPos) {
-    match moves.first() {
-        Some(mv) => {
-            let (new_ship, new_waypoint) = calculate_waypoint_positions(ship, waypoint, *mv);
-            return perform_waypoint_moves(&moves[1..], new_ship, new_waypoint);
-        }
-        None => return (ship, waypoint),
+    let mut state = (ship, waypoint);
+    for mv in moves {
+        state = calculate_waypoint_positions(state.0, state.1, *mv);
+    }
+    return state;
+}
+
+fn precise_manhattan_distance(pos: PrecisePos) -> f64 {
+    return pos.0.abs() + pos.1.abs();
+}
+
+/// Adds a move of any distance, split into as many moves as it takes for
+/// each one to fit
+fn push_long_move(moves: &mut Vec<Move>, distance: u64, mv: fn(u32) -> Move) {
+    let mut left = distance;
+    while left > 0 {
+        let step = u32::try_from(left).unwrap_or(u32::MAX);
+        moves.push(mv(step));
+        left -= step as u64;
+    }
+}
+
+/// Adds moves east or west then north or south, leaving out ones that go
+/// nowhere
+fn push_translation(moves: &mut Vec<Move>, ew: i64, ns: i64) {
+    let east_west = if ew > 0 { Move::East } else { Move::West };
+    push_long_move(moves, ew.unsigned_abs(), east_west);
+    let north_south = if ns > 0 { Move::North } else { Move::South };
+    push_long_move(moves, ns.unsigned_abs(), north_south);
+}
+
+/// Adds the moves for a turn of the waypoint followed by a shift of it
+fn push_waypoint_block(moves: &mut Vec<Move>, turn: u16, shift: (i64, i64)) {
+    if let Some(mv) = turn_between(0, turn) {
+        push_waypoint_move(moves, mv);
     }
+    push_translation(moves, shift.0, shift.1);
+}
+
+/// Adds a turn or forward move, merging it into the moves before it where
+/// that doesn't change where the ship or the waypoint end up, and where the
+/// merged move still fits
+fn push_waypoint_move(moves: &mut Vec<Move>, mv: Move) {
+    match (moves.as_slice(), mv) {
+        ([.., Move::Forward(a)], Move::Forward(b)) if a.checked_add(b).is_some() => {
+            let sum = a + b;
+            moves.pop();
+            moves.push(Move::Forward(sum));
+        },
+        // Going forward, turning round and going forward again is the same
+        // as going forward the difference and turning round
+        ([.., Move::Forward(a), Move::Right(180)], Move::Forward(b)) => {
+            let a = *a;
+            moves.truncate(moves.len() - 2);
+            if a > b {
+                push_waypoint_move(moves, Move::Forward(a - b));
+                push_waypoint_move(moves, Move::Right(180));
+            } else {
+                push_waypoint_move(moves, Move::Right(180));
+                if b > a {
+                    push_waypoint_move(moves, Move::Forward(b - a));
+                }
+            }
+        },
+        ([.., Move::Left(a)], Move::Left(_) | Move::Right(_)) |
+        ([.., Move::Right(a)], Move::Left(_) | Move::Right(_)) => {
+            let first = if let Some(Move::Left(_)) = moves.last() { sub_degrees(0, *a) } else { *a };
+            let second = match mv {
+                Move::Left(v) => sub_degrees(0, v),
+                Move::Right(v) => v,
+                _ => unreachable!(),
+            };
+            moves.pop();
+            moves.extend(turn_between(0, add_degrees(first, second)));
+        },
+        _ => moves.push(mv),
+    }
+}
+
+/// Like `perform_moves`, but keeps every position along the way
+fn record_moves(moves: &[Move], ew: i32, ns: i32, dir: u16) -> Route {
+    let mut ships = Vec::with_capacity(moves.len() + 1);
+    let mut state = (ew, ns, dir);
+    ships.push((ew, ns));
+    for mv in moves {
+        state = calculate_position(state.0, state.1, state.2, *mv);
+        ships.push((state.0, state.1));
+    }
+    return Route {ships, waypoints: None};
+}
+
+/// Like `perform_waypoint_moves`, but keeps every position along the way
+fn record_waypoint_moves(moves: &[Move], ship: ShipPos, waypoint: WaypointPos) -> Route {
+    let mut ships = Vec::with_capacity(moves.len() + 1);
+    let mut waypoints = Vec::with_capacity(moves.len() + 1);
+    let mut state = (ship, waypoint);
+    ships.push(ship);
+    waypoints.push(waypoint);
+    for mv in moves {
+        state = calculate_waypoint_positions(state.0, state.1, *mv);
+        ships.push(state.0);
+        waypoints.push(state.1);
+    }
+    return Route {ships, waypoints: Some(waypoints)};
+}
+
+/// Moves that undo `moves`, bringing the ship back to where it started,
+/// facing the same way, in either mode. Turning round, going forward and
+/// turning back undoes going forward.
+fn reverse_moves(moves: &[Move]) -> Vec<Move> {
+    return moves.iter().rev().flat_map(|mv| match *mv {
+        Move::North(v) => vec![Move::South(v)],
+        Move::South(v) => vec![Move::North(v)],
+        Move::East(v) => vec![Move::West(v)],
+        Move::West(v) => vec![Move::East(v)],
+        Move::Left(v) => vec![Move::Right(v)],
+        Move::Right(v) => vec![Move::Left(v)],
+        Move::Forward(v) => vec![Move::Right(180), Move::Forward(v), Move::Left(180)],
+    }).collect();
 }
 
-fn rotate_waypoint(pos: WaypointPos, angle: i16) -> WaypointPos {
+/// Rotates clockwise by a multiple of 90 degrees
+fn rotate_waypoint<T: Neg<Output = T>>(pos: (T, T), angle: i32) -> (T, T) {
     let (ew, ns) = pos;
-    let rotation = (angle as f32).to_radians();
+    match angle.rem_euclid(360) {
+        0 => (ew, ns),
+        90 => (ns, -ew),
+        180 => (-ew, -ns),
+        270 => (-ns, ew),
+        _ => panic!("Unexpected angle: {} (use precise navigation for other angles)", angle),
+    }
+}
 
-    let cos = rotation.cos();
-    let sin = rotation.sin();
-    let new_ew = (cos * ew as f32) + (sin * ns as f32);
-    let new_ns = -(sin * ew as f32) + (cos * ns as f32);
-    return (new_ew.round() as i32, new_ns.round() as i32);
+/// Rotates clockwise by any angle
+fn rotate_precise_waypoint(pos: PrecisePos, angle: f64) -> PrecisePos {
+    let (ew, ns) = pos;
+    let (sin, cos) = sin_cos_degrees(angle);
+    let new_ew = (cos * ew) + (sin * ns);
+    let new_ns = -(sin * ew) + (cos * ns);
+    return (new_ew, new_ns);
 }
 
 fn run_navigation(moves: &[Move]) -> (i32, i32) {
@@ -133,10 +485,46 @@ fn run_waypoint_navigation(moves: &[Move]) -> ShipPos {
     return ship;
 }
 
+fn run_precise_navigation(moves: &[Move]) -> PrecisePos {
+    let (pos, _heading) = moves.iter().fold(((0.0, 0.0), 90.0), |(pos, heading), &mv| {
+        calculate_precise_position(pos, heading, mv)
+    });
+    return pos;
+}
+
+fn run_precise_waypoint_navigation(moves: &[Move]) -> PrecisePos {
+    let (ship, _waypoint) = moves.iter().fold(((0.0, 0.0), (10.0, 1.0)), |(ship, waypoint), &mv| {
+        calculate_precise_waypoint_positions(ship, waypoint, mv)
+    });
+    return ship;
+}
+
+/// Sine and cosine of an angle in degrees, exact for multiples of 90
+fn sin_cos_degrees(degrees: f64) -> (f64, f64) {
+    let degrees = degrees.rem_euclid(360.0);
+    if degrees == 0.0 {
+        return (0.0, 1.0);
+    } else if degrees == 90.0 {
+        return (1.0, 0.0);
+    } else if degrees == 180.0 {
+        return (0.0, -1.0);
+    } else if degrees == 270.0 {
+        return (-1.0, 0.0);
+    }
+    return degrees.to_radians().sin_cos();
+}
+
 fn sub_degrees(first: u16, second: u16) -> u16 {
-    match first.checked_sub(second) {
-        Some(v) => v,
-        None => (first + 360) - second,
+    let degrees = (first as i32 - second as i32).rem_euclid(360);
+    return degrees as u16;
+}
+
+/// The shortest turn from one heading to another, if they differ
+fn turn_between(from: u16, to: u16) -> Option<Move> {
+    match sub_degrees(to, from) {
+        0 => None,
+        270 => Some(Move::Left(90)),
+        degrees => Some(Move::Right(degrees)),
     }
 }
 
@@ -152,13 +540,67 @@ fn part2(moves: &[Move]) {
     println!("Distance for part 2: {}", dist);
 }
 
+fn precise_parts(moves: &[Move]) {
+    let dist = precise_manhattan_distance(run_precise_navigation(moves));
+    println!("Distance for part 1: {}", dist);
+    let dist = precise_manhattan_distance(run_precise_waypoint_navigation(moves));
+    println!("Distance for part 2: {}", dist);
+}
+
+fn print_moves(moves: &[Move]) {
+    for mv in moves {
+        println!("{}", mv);
+    }
+}
+
+fn print_route(moves: &[Move], args: &[String]) {
+    let waypoint = args.iter().any(|a| a == "--waypoint");
+    let compress = if waypoint { compress_waypoint_moves } else { compress_moves };
+    if args.iter().any(|a| a == "--compress") {
+        print_moves(&compress(moves));
+    }
+    if args.iter().any(|a| a == "--reverse") {
+        print_moves(&compress(&reverse_moves(moves)));
+    }
+
+    let route = if waypoint {
+        record_waypoint_moves(moves, (0, 0), (10, 1))
+    } else {
+        record_moves(moves, 0, 0, 90)
+    };
+    if args.iter().any(|a| a == "--stats") {
+        println!("Furthest from origin: {}", route.max_distance());
+        println!("Total distance travelled: {}", route.total_distance());
+    }
+    match args.iter().find_map(|a| a.strip_prefix("--route=")) {
+        Some("csv") => print!("{}", route.to_csv()),
+        Some("svg") => print!("{}", route.to_svg()),
+        Some(format) => panic!("Unknown route format: {}", format),
+        None => (),
+    }
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
     let moves = parse_moves(&input);
 
-    part1(&moves);
-    part2(&moves);
+    // Turns that aren't right angles need the floating point navigation
+    let precise = args.iter().any(|a| a == "--precise");
+    if precise || !moves.iter().all(Move::is_right_angle) {
+        // Routes are only recorded on the integer grid
+        let route_flags = ["--compress", "--reverse", "--stats"];
+        if let Some(flag) = args.iter().find(|a| route_flags.contains(&a.as_str()) || a.starts_with("--route=")) {
+            eprintln!("{} only works with right angle turns and without --precise", flag);
+            process::exit(1);
+        }
+        precise_parts(&moves);
+    } else {
+        part1(&moves);
+        part2(&moves);
+        print_route(&moves, &args);
+    }
 }
 
 #[cfg(test)]
@@ -168,11 +610,36 @@ mod tests {
     #[test]
     fn test_add_degrees() {
         assert_eq!(add_degrees(270, 180), 90);
+        assert_eq!(add_degrees(90, 360), 90);
+        assert_eq!(add_degrees(270, 810), 0);
+        assert_eq!(add_degrees(u16::MAX, u16::MAX), 30);
     }
 
     #[test]
     fn test_sub_degrees() {
         assert_eq!(sub_degrees(90, 180), 270);
+        assert_eq!(sub_degrees(90, 450), 0);
+        assert_eq!(sub_degrees(0, 1080), 0);
+        assert_eq!(sub_degrees(0, u16::MAX), 345);
+    }
+
+    #[test]
+    fn test_calculate_position_full_turns() {
+        let result = calculate_position(0, 0, 90, Move::Left(720));
+        assert_eq!(result, (0, 0, 90));
+        let result = calculate_position(0, 0, 90, Move::Right(450));
+        assert_eq!(calculate_position(result.0, result.1, result.2, Move::Forward(2)), (0, -2, 180));
+    }
+
+    #[test]
+    fn test_calculate_precise_position() {
+        let (pos, heading) = calculate_precise_position((0.0, 0.0), 90.0, Move::Left(45));
+        assert_eq!(heading, 45.0);
+        let (pos, _) = calculate_precise_position(pos, heading, Move::Forward(2));
+        assert!((pos.0 - 2f64.sqrt()).abs() < 1e-12);
+        assert!((pos.1 - 2f64.sqrt()).abs() < 1e-12);
+        let (_, heading) = calculate_precise_position(pos, 10.0, Move::Left(400));
+        assert_eq!(heading, 330.0);
     }
 
     #[test]
@@ -208,12 +675,16 @@ mod tests {
     #[test]
     fn test_manhattan_distance() {
         assert_eq!(manhattan_distance(17, -8), 25);
+        assert_eq!(manhattan_distance(i32::MAX, i32::MIN), u32::MAX as u64);
     }
 
     #[test]
     fn test_parse_move() {
         let mv = parse_move("R90");
         assert_eq!(mv, Move::Right(90));
+        assert_eq!(parse_move("R65626"), Move::Right(106));
+        assert_eq!(parse_move("L450"), Move::Left(90));
+        assert!(!parse_move("R65626").is_right_angle());
     }
 
     #[test]
@@ -237,6 +708,20 @@ F11";
     fn test_rotate_waypoint() {
         let result = rotate_waypoint((10, 4), 90);
         assert_eq!(result, (4, -10));
+        assert_eq!(rotate_waypoint((10, 4), -90), (-4, 10));
+        assert_eq!(rotate_waypoint((10, 4), 180), (-10, -4));
+        assert_eq!(rotate_waypoint((10, 4), 630), (-4, 10));
+        assert_eq!(rotate_waypoint((10, 4), -720), (10, 4));
+        assert_eq!(rotate_waypoint((i32::MAX, 7), 90), (7, -i32::MAX));
+    }
+
+    #[test]
+    fn test_rotate_precise_waypoint() {
+        assert_eq!(rotate_precise_waypoint((10.0, 4.0), 90.0), (4.0, -10.0));
+        assert_eq!(rotate_precise_waypoint((10.0, 4.0), -270.0), (4.0, -10.0));
+        let (ew, ns) = rotate_precise_waypoint((0.0, 2.0), 60.0);
+        assert!((ew - 3f64.sqrt()).abs() < 1e-12);
+        assert!((ns - 1.0).abs() < 1e-12);
     }
 
     #[test]
@@ -250,4 +735,155 @@ F11";
         ];
         assert_eq!(run_waypoint_navigation(&moves), (214, -72));
     }
+
+    #[test]
+    fn test_perform_many_moves() {
+        let moves: Vec<Move> = (0..1_000_000).map(|i| match i % 4 {
+            0 => Move::Forward(1),
+            1 => Move::Left(90),
+            2 => Move::North(2),
+            _ => Move::Right(180),
+        }).collect();
+        assert_eq!(perform_moves(&moves, 0, 0, 90), (0, 500000, 90));
+        let (ship, _) = perform_waypoint_moves(&moves, (0, 0), (10, 1));
+        assert_eq!(ship, run_waypoint_navigation(&moves));
+    }
+
+    #[test]
+    fn test_record_moves() {
+        let moves = parse_moves("F10\nN3\nF7\nR90\nF11");
+        let route = record_moves(&moves, 0, 0, 90);
+        assert_eq!(route.ships, vec![(0, 0), (10, 0), (10, 3), (17, 3), (17, 3), (17, -8)]);
+        assert_eq!(route.waypoints, None);
+        assert_eq!(route.max_distance(), 25);
+        assert_eq!(route.total_distance(), 31);
+    }
+
+    #[test]
+    fn test_record_waypoint_moves() {
+        let moves = parse_moves("F10\nN3\nF7\nR90\nF11");
+        let route = record_waypoint_moves(&moves, (0, 0), (10, 1));
+        assert_eq!(route.ships, vec![(0, 0), (100, 10), (100, 10), (170, 38), (170, 38), (214, -72)]);
+        assert_eq!(route.waypoints, Some(vec![(10, 1), (10, 1), (10, 4), (10, 4), (4, -10), (4, -10)]));
+        assert_eq!(route.max_distance(), 286);
+        assert_eq!(route.total_distance(), 110 + 98 + 154);
+    }
+
+    #[test]
+    fn test_route_exports() {
+        let route = record_waypoint_moves(&parse_moves("F2\nR90"), (0, 0), (3, 1));
+        assert_eq!(route.to_csv(), "step,ship_ew,ship_ns,waypoint_ew,waypoint_ns
+0,0,0,3,1
+1,6,2,3,1
+2,6,2,1,-3
+");
+        let route = record_moves(&parse_moves("F2\nS3"), 0, 0, 90);
+        assert_eq!(route.to_csv(), "step,ship_ew,ship_ns\n0,0,0\n1,2,0\n2,2,-3\n");
+        assert_eq!(route.to_svg(), "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -1 4 5\">
+  <polyline fill=\"none\" stroke=\"black\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\" points=\"0,0 2,0 2,3\"/>
+</svg>
+");
+    }
+
+    /// Right-angle moves from a small linear congruential generator
+    fn random_moves(seed: u64, count: usize) -> Vec<Move> {
+        let mut state = seed;
+        return (0..count).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let value = (state >> 33) as u32;
+            match value % 7 {
+                0 => Move::North(value % 5),
+                1 => Move::South(value % 5),
+                2 => Move::East(value % 5),
+                3 => Move::West(value % 5),
+                4 => Move::Left((value % 4 * 90) as u16),
+                5 => Move::Right((value % 9 * 90) as u16),
+                _ => Move::Forward(value % 6),
+            }
+        }).collect();
+    }
+
+    #[test]
+    fn test_compress_moves() {
+        let moves = parse_moves("F10\nN3\nF7\nR90\nF11");
+        assert_eq!(compress_moves(&moves), parse_moves("N3\nF17\nR90\nF11"));
+        let moves = parse_moves("F5\nL90\nL90\nF8\nR360\nE2\nW2\nR180");
+        assert_eq!(compress_moves(&moves), parse_moves("R180\nF3\nR180"));
+        let moves = parse_moves("R90\nF2\nL90");
+        assert_eq!(compress_moves(&moves), moves);
+    }
+
+    #[test]
+    fn test_compress_waypoint_moves() {
+        let moves = parse_moves("F10\nR180\nF4\nL90\nL90\nN2\nE3\nS2");
+        assert_eq!(compress_waypoint_moves(&moves), parse_moves("F6\nE3"));
+        let moves = parse_moves("N1\nR90\nE2\nF3\nF4\nL90\nR90");
+        assert_eq!(compress_waypoint_moves(&moves), parse_moves("R90\nE3\nF7"));
+        let moves = parse_moves("F10\nN3\nF7\nR90\nF11");
+        assert_eq!(compress_waypoint_moves(&moves), moves);
+    }
+
+    #[test]
+    fn test_compress_long_moves() {
+        let max = u32::MAX;
+        let moves = vec![Move::Forward(max), Move::Right(360), Move::Forward(max), Move::North(0)];
+        assert_eq!(compress_moves(&moves), vec![Move::Forward(max), Move::Forward(max)]);
+        let moves = vec![Move::East(max), Move::North(0), Move::East(3), Move::South(0)];
+        assert_eq!(compress_moves(&moves), vec![Move::East(max), Move::East(3)]);
+
+        let moves = vec![Move::Forward(max), Move::North(0), Move::Forward(5), Move::North(0)];
+        assert_eq!(compress_waypoint_moves(&moves), vec![Move::Forward(max), Move::Forward(5)]);
+        let moves = vec![Move::West(max), Move::West(max), Move::Left(90), Move::Right(90)];
+        assert_eq!(compress_waypoint_moves(&moves), vec![Move::West(max), Move::West(max)]);
+    }
+
+    #[test]
+    fn test_compress_random_moves() {
+        for seed in 0..200 {
+            let moves = random_moves(seed, (seed % 40) as usize);
+            let compressed = compress_moves(&moves);
+            assert!(compressed.len() <= moves.len());
+            for &(ew, ns, dir) in &[(0, 0, 90), (3, -2, 270), (-5, 1, 0)] {
+                assert_eq!(perform_moves(&compressed, ew, ns, dir), perform_moves(&moves, ew, ns, dir));
+            }
+
+            let compressed = compress_waypoint_moves(&moves);
+            assert!(compressed.len() <= moves.len());
+            for &(ship, waypoint) in &[((0, 0), (10, 1)), ((4, -7), (-3, 2))] {
+                assert_eq!(perform_waypoint_moves(&compressed, ship, waypoint),
+                    perform_waypoint_moves(&moves, ship, waypoint));
+            }
+        }
+    }
+
+    #[test]
+    fn test_reverse_moves() {
+        assert_eq!(reverse_moves(&parse_moves("N3\nF2\nL90")), parse_moves("R90\nR180\nF2\nL180\nS3"));
+        for seed in 0..200 {
+            let moves = random_moves(seed, 30);
+            let (ew, ns, dir) = perform_moves(&moves, 0, 0, 90);
+            let back = compress_moves(&reverse_moves(&moves));
+            assert_eq!(perform_moves(&back, ew, ns, dir), (0, 0, 90));
+
+            let (ship, waypoint) = perform_waypoint_moves(&moves, (0, 0), (10, 1));
+            let back = compress_waypoint_moves(&reverse_moves(&moves));
+            assert_eq!(perform_waypoint_moves(&back, ship, waypoint), ((0, 0), (10, 1)));
+        }
+    }
+
+    #[test]
+    fn test_display_move() {
+        let moves = parse_moves("F10\nN3\nS4\nE1\nW7\nL270\nR90");
+        let lines: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(lines.join("\n"), "F10\nN3\nS4\nE1\nW7\nL270\nR90");
+    }
+
+    #[test]
+    fn test_precise_navigation_matches() {
+        let moves = parse_moves("F10\nN3\nF7\nR90\nF11\nL270\nF5\nR180\nW4\nF2");
+        let (ew, ns) = run_navigation(&moves);
+        assert_eq!(run_precise_navigation(&moves), (ew as f64, ns as f64));
+        let (ew, ns) = run_waypoint_navigation(&moves);
+        assert_eq!(run_precise_waypoint_navigation(&moves), (ew as f64, ns as f64));
+    }
 }
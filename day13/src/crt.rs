@@ -0,0 +1,194 @@
+use num_bigint::{BigInt, BigUint, Sign};
+use std::fmt;
+
+/// Every number congruent to `residue` modulo `modulus`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Congruence {
+    pub residue: BigUint,
+    pub modulus: BigUint,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CrtError {
+    /// Nothing satisfies this congruence as well as the ones before it
+    NoSolution(usize),
+    /// This congruence has a modulus of zero
+    ZeroModulus(usize),
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrtError::NoSolution(index) => {
+                write!(f, "Congruence {} can't hold alongside the ones before it", index)
+            },
+            CrtError::ZeroModulus(index) => write!(f, "Congruence {} has a modulus of zero", index),
+        }
+    }
+}
+
+/// Solves `x = residue (mod modulus)` for every pair at once, merging them
+/// one at a time. The moduli don't need to be coprime. Works in `i128` while
+/// the numbers fit, and starts again with big integers if they don't.
+pub fn solve(congruences: &[(i64, u64)]) -> Result<Congruence, CrtError> {
+    if let Some(index) = congruences.iter().position(|&(_, modulus)| modulus == 0) {
+        return Err(CrtError::ZeroModulus(index));
+    }
+    return match solve_small(congruences) {
+        Some(Ok((residue, modulus))) => Ok(Congruence {
+            residue: BigUint::from(residue as u128),
+            modulus: BigUint::from(modulus as u128),
+        }),
+        Some(Err(e)) => Err(e),
+        None => solve_big(congruences),
+    };
+}
+
+/// `None` if anything overflows
+fn solve_small(congruences: &[(i64, u64)]) -> Option<Result<(i128, i128), CrtError>> {
+    let mut merged = (0, 1);
+    for (index, &(residue, modulus)) in congruences.iter().enumerate() {
+        let modulus = modulus as i128;
+        let next = ((residue as i128).rem_euclid(modulus), modulus);
+        merged = match merge_small(merged, next)? {
+            Some(merged) => merged,
+            None => return Some(Err(CrtError::NoSolution(index))),
+        };
+    }
+    return Some(Ok(merged));
+}
+
+/// The congruence that holds exactly when both do, `Some(None)` if there
+/// isn't one, or `None` on overflow
+fn merge_small(first: (i128, i128), second: (i128, i128)) -> Option<Option<(i128, i128)>> {
+    let (r1, m1) = first;
+    let (r2, m2) = second;
+    let (gcd, inverse, _) = egcd(m1, m2);
+    let diff = r2 - r1;
+    if diff % gcd != 0 {
+        return Some(None);
+    }
+    // m1 * inverse = gcd (mod m2), so stepping r1 by m1 * k where
+    // k = (diff / gcd) * inverse (mod m2 / gcd) lands on r2 modulo m2
+    let step = m2 / gcd;
+    let k = (diff / gcd).rem_euclid(step).checked_mul(inverse.rem_euclid(step))? % step;
+    let lcm = m1.checked_mul(step)?;
+    let residue = r1.checked_add(m1.checked_mul(k)?)?.rem_euclid(lcm);
+    return Some(Some((residue, lcm)));
+}
+
+fn solve_big(congruences: &[(i64, u64)]) -> Result<Congruence, CrtError> {
+    let mut merged = (BigInt::from(0), BigInt::from(1));
+    for (index, &(residue, modulus)) in congruences.iter().enumerate() {
+        let modulus = BigInt::from(modulus);
+        let next = (rem_euclid_big(&BigInt::from(residue), &modulus), modulus);
+        merged = match merge_big(&merged, &next) {
+            Some(merged) => merged,
+            None => return Err(CrtError::NoSolution(index)),
+        };
+    }
+    let (residue, modulus) = merged;
+    return Ok(Congruence {
+        residue: residue.to_biguint().unwrap(),
+        modulus: modulus.to_biguint().unwrap(),
+    });
+}
+
+/// Same as `merge_small`, without the overflow
+fn merge_big(first: &(BigInt, BigInt), second: &(BigInt, BigInt)) -> Option<(BigInt, BigInt)> {
+    let (r1, m1) = first;
+    let (r2, m2) = second;
+    let (gcd, inverse) = egcd_big(m1, m2);
+    let diff = r2 - r1;
+    if (&diff % &gcd).sign() != Sign::NoSign {
+        return None;
+    }
+    let step = m2 / &gcd;
+    let k = rem_euclid_big(&(&diff / &gcd * inverse), &step);
+    let lcm = m1 * &step;
+    let residue = rem_euclid_big(&(r1 + m1 * k), &lcm);
+    return Some((residue, lcm));
+}
+
+fn rem_euclid_big(a: &BigInt, m: &BigInt) -> BigInt {
+    let rem = a % m;
+    if rem.sign() == Sign::Minus {
+        return rem + m;
+    }
+    return rem;
+}
+
+/// The greatest common divisor of `a` and `b`, and `x` and `y` with
+/// `a * x + b * y` equal to it
+fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    return (old_r, old_x, old_y);
+}
+
+/// Same as `egcd` for non-negative big integers, without `y`
+fn egcd_big(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
+    let (mut old_r, mut r) = (a.clone(), b.clone());
+    let (mut old_x, mut x) = (BigInt::from(1), BigInt::from(0));
+    while r.sign() != Sign::NoSign {
+        let q = &old_r / &r;
+        let next_r = &old_r - &q * &r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_x = &old_x - &q * &x;
+        old_x = std::mem::replace(&mut x, next_x);
+    }
+    return (old_r, old_x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn congruence(residue: u128, modulus: u128) -> Congruence {
+        return Congruence {residue: BigUint::from(residue), modulus: BigUint::from(modulus)};
+    }
+
+    #[test]
+    fn test_solve_coprime() {
+        assert_eq!(solve(&[(0, 17), (-2, 13), (-3, 19)]), Ok(congruence(3417, 4199)));
+        assert_eq!(solve(&[(2, 3), (3, 5), (2, 7)]), Ok(congruence(23, 105)));
+        assert_eq!(solve(&[]), Ok(congruence(0, 1)));
+    }
+
+    #[test]
+    fn test_solve_shared_factors() {
+        assert_eq!(solve(&[(2, 4), (4, 6)]), Ok(congruence(10, 12)));
+        assert_eq!(solve(&[(3, 6), (3, 6), (1, 2)]), Ok(congruence(3, 6)));
+        assert_eq!(solve(&[(1, 4), (2, 6)]), Err(CrtError::NoSolution(1)));
+        assert_eq!(solve(&[(1, 5), (1, 4), (0, 10)]), Err(CrtError::NoSolution(2)));
+        assert_eq!(solve(&[(1, 5), (0, 0)]), Err(CrtError::ZeroModulus(1)));
+    }
+
+    #[test]
+    fn test_solve_big() {
+        // Primes whose product is well past what fits in an i128
+        let primes: Vec<u64> = (2..400)
+            .filter(|&n| (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0))
+            .collect();
+        let congruences: Vec<(i64, u64)> = primes.iter().enumerate()
+            .map(|(i, &p)| (-(i as i64), p))
+            .collect();
+        assert!(solve_small(&congruences).is_none());
+        let solution = solve(&congruences).unwrap();
+        let product = primes.iter().fold(BigUint::from(1u32), |acc, &p| acc * p);
+        assert_eq!(solution.modulus, product);
+        for &(residue, modulus) in &congruences {
+            let expected = BigUint::from(residue.rem_euclid(modulus as i64) as u64);
+            assert_eq!(&solution.residue % modulus, expected);
+        }
+        assert_eq!(solve_big(&[(2, 4), (4, 6)]), Ok(congruence(10, 12)));
+        assert_eq!(solve_big(&[(1, 4), (2, 6)]), Err(CrtError::NoSolution(1)));
+    }
+}
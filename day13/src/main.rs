@@ -1,68 +1,75 @@
+use num_bigint::BigUint;
+use std::env;
+use std::fmt;
 use std::io::{self, Read};
+use std::process;
 
-fn calculate_next_arrivals(bus_ids: &[u32], earliest: u32) -> Vec<(u32, u32)> {
-    let arrivals = bus_ids.iter().map(|id| {
-        let next = ((earliest / id) + 1) * id;
-        (*id, next)
+use crt::CrtError;
+use timetable::Timetable;
+
+mod crt;
+mod timetable;
+
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    /// The bus at this place in the schedule has an ID of zero
+    ZeroBusId(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::ZeroBusId(index) => write!(f, "Bus {} in the schedule has an ID of zero", index),
+        }
+    }
+}
+
+/// The next bus of each ID leaving strictly after `earliest`. Unlike the
+/// timetable queries, a bus leaving at `earliest` itself has already gone.
+fn calculate_next_arrivals(bus_ids: &[u64], earliest: u64) -> Vec<(u64, u64)> {
+    let arrivals = bus_ids.iter().map(|&id| {
+        (id, timetable::next_departure(id, earliest + 1))
     }).collect();
     return arrivals;
 }
 
-fn find_earliest_arrival(arrivals: &[(u32, u32)]) -> (u32, u32) {
+fn find_earliest_arrival(arrivals: &[(u64, u64)]) -> (u64, u64) {
     let earliest = arrivals.iter().min_by_key(|a| a.1).unwrap().to_owned();
     return earliest;
 }
 
-fn find_earliest_timestamp(constraints: &[Option<u64>]) -> i128 {
-    let active: Vec<_> = constraints.iter().enumerate().filter_map(|(i, &c)| {
-        match c {
-            Some(id) => Some((i as i128, id as i128)),
-            None => None,
-        }
-    }).collect();
-    let n: i128 = active.iter().map(|&(_, id)| id).product();
-    let rem: i128 = active.iter().map(|&(i, id)| {
-        let y = n / id;
-        i * modinv(y, id).unwrap() * y
-    }).sum();
-    return n - (rem % n);
+/// The earliest timestamp where each bus leaves as many minutes after it as
+/// its place in the list
+fn find_earliest_timestamp(constraints: &[Option<u64>]) -> Result<BigUint, CrtError> {
+    let offsets = Timetable::from_constraints(constraints).schedule_offsets();
+    return timetable::align(&offsets, &BigUint::from(0u32));
 }
 
-fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
-    if a == 0 {
-        return (b, 0, 1);
-    } else {
-        let (g, x, y) = egcd(b % a, a);
-        return (g, y - ((b / a) * x), x);
-    }
-}
-
-fn modinv(a: i128, m: i128) -> Option<i128> {
-    let (gcd, x, _y) = egcd(a, m);
-    if gcd != 1 {
-        return None;
-    } else {
-        return Some((x % m + m) % m);
-    }
+/// Bus IDs each with an offset, like `7:0,13:1,59:-4`
+fn parse_offsets(offsets_str: &str) -> Vec<(u64, i64)> {
+    return offsets_str.split(',').map(|o| {
+        let (id, offset) = o.split_once(':').unwrap();
+        (id.parse().unwrap(), offset.parse().unwrap())
+    }).collect();
 }
 
-fn parse_constraints(input: &str) -> Vec<Option<u64>> {
+fn parse_constraints(input: &str) -> Result<Vec<Option<u64>>, ParseError> {
     let constraints_str = input.lines().nth(1).unwrap();
-    let constraints = constraints_str.split(',').map(|c| c.parse().ok()).collect();
-    return constraints;
+    let constraints: Vec<Option<u64>> = constraints_str.split(',').map(|c| c.parse().ok()).collect();
+    if let Some(index) = constraints.iter().position(|&c| c == Some(0)) {
+        return Err(ParseError::ZeroBusId(index));
+    }
+    return Ok(constraints);
 }
 
-fn parse_input(input: &str) -> (u32, Vec<u32>) {
+fn parse_input(input: &str) -> Result<(u64, Vec<u64>), ParseError> {
     let earliest_str = input.lines().nth(0).unwrap();
-    let bus_ids_str = input.lines().nth(1).unwrap();
     let earliest = earliest_str.parse().unwrap();
-    let bus_ids = bus_ids_str.split(',').filter_map(|id_str| {
-        id_str.parse().ok()
-    }).collect();
-    return (earliest, bus_ids);
+    let bus_ids = parse_constraints(input)?.into_iter().flatten().collect();
+    return Ok((earliest, bus_ids));
 }
 
-fn part1(bus_ids: &[u32], earliest: u32) {
+fn part1(bus_ids: &[u64], earliest: u64) {
     let arrivals = calculate_next_arrivals(bus_ids, earliest);
     let (bus_id, next_arrival) = find_earliest_arrival(&arrivals);
     let wait = next_arrival - earliest;
@@ -71,18 +78,63 @@ fn part1(bus_ids: &[u32], earliest: u32) {
 }
 
 fn part2(constraints: &[Option<u64>]) {
-    let timestamp = find_earliest_timestamp(constraints);
-    println!("Earliest timestamp for part 2: {}", timestamp);
+    print_or_exit("Earliest timestamp for part 2", find_earliest_timestamp(constraints));
+}
+
+fn print_or_exit(label: &str, result: Result<BigUint, CrtError>) {
+    match result {
+        Ok(timestamp) => println!("{}: {}", label, timestamp),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    }
+}
+
+fn run_queries(timetable: &Timetable, earliest: u64, args: &[String]) {
+    let value = |name: &str| {
+        let prefix = format!("--{}=", name);
+        args.iter().find_map(|a| a.strip_prefix(&prefix))
+    };
+    let from = value("from").map_or(earliest, |t| t.parse().unwrap());
+
+    if let Some(count) = value("departures") {
+        for (time, id) in timetable.next_departures(from, count.parse().unwrap()) {
+            println!("{}: bus {}", time, id);
+        }
+    }
+    if let Some(time) = value("at") {
+        let buses: Vec<String> = timetable.departing_at(time.parse().unwrap())
+            .iter().map(|id| id.to_string()).collect();
+        println!("Buses leaving at {}: {}", time, buses.join(","));
+    }
+    if let Some(ids) = value("together") {
+        let ids: Vec<u64> = ids.split(',').map(|id| id.parse().unwrap()).collect();
+        print_or_exit("Buses next leave together at", timetable::next_together(&ids, &BigUint::from(from)));
+    }
+    if let Some(offsets) = value("align") {
+        let offsets = parse_offsets(offsets);
+        print_or_exit("Buses next line up at", timetable::align(&offsets, &BigUint::from(from)));
+    }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap();
-    let (earliest, bus_ids) = parse_input(&input);
-    let constraints = parse_constraints(&input);
+    let (earliest, bus_ids) = match parse_input(&input) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        },
+    };
+    let constraints = parse_constraints(&input).unwrap();
 
     part1(&bus_ids, earliest);
     part2(&constraints);
+
+    run_queries(&Timetable::from_constraints(&constraints), earliest, &args);
 }
 
 #[cfg(test)]
@@ -95,6 +147,7 @@ mod tests {
         let expected = vec![(7, 945), (13, 949), (59, 944)];
         let actual = calculate_next_arrivals(&bus_ids, 939);
         assert_eq!(actual, expected);
+        assert_eq!(calculate_next_arrivals(&[7, 13], 91), vec![(7, 98), (13, 104)]);
     }
 
     #[test]
@@ -110,21 +163,35 @@ mod tests {
 7,13,x,x,59,x,31,19";
         let expected = (939, vec![7, 13, 59, 31, 19]);
         let actual = parse_input(input);
-        assert_eq!(actual, expected);
+        assert_eq!(actual, Ok(expected));
+        assert_eq!(parse_input("939\n7,x,0"), Err(ParseError::ZeroBusId(2)));
     }
 
     #[test]
     fn test_find_earliest_timestamp_1() {
         let constraints = vec![Some(17), None, Some(13), Some(19)];
         let earliest = find_earliest_timestamp(&constraints);
-        assert_eq!(earliest, 3417);
+        assert_eq!(earliest, Ok(BigUint::from(3417u32)));
     }
 
     #[test]
     fn test_find_earliest_timestamp_2() {
         let constraints = vec![Some(1789), Some(37), Some(47), Some(1889)];
         let earliest = find_earliest_timestamp(&constraints);
-        assert_eq!(earliest, 1_202_161_486);
+        assert_eq!(earliest, Ok(BigUint::from(1_202_161_486u32)));
+    }
+
+    #[test]
+    fn test_find_earliest_timestamp_shared_factors() {
+        let constraints = vec![Some(4), None, Some(6), None, Some(10)];
+        assert_eq!(find_earliest_timestamp(&constraints), Ok(BigUint::from(16u32)));
+        let constraints = vec![Some(4), Some(6)];
+        assert_eq!(find_earliest_timestamp(&constraints), Err(CrtError::NoSolution(1)));
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        assert_eq!(parse_offsets("7:0,13:1,59:-4"), vec![(7, 0), (13, 1), (59, -4)]);
     }
 
     #[test]
@@ -133,6 +200,6 @@ mod tests {
 7,13,x,x,59,x,31,19";
         let expected = vec![Some(7), Some(13), None, None, Some(59), None, Some(31), Some(19)];
         let actual = parse_constraints(input);
-        assert_eq!(actual, expected);
+        assert_eq!(actual, Ok(expected));
     }
 }
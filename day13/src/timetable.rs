@@ -0,0 +1,134 @@
+use num_bigint::BigUint;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::crt::{self, CrtError};
+
+/// The buses in service, each leaving every `id` minutes from timestamp 0,
+/// along with where they appear in the schedule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timetable {
+    buses: Vec<(usize, u64)>,
+}
+
+impl Timetable {
+    /// Builds the timetable from a schedule where `None` is an `x`. Bus IDs
+    /// have to be more than zero.
+    pub fn from_constraints(constraints: &[Option<u64>]) -> Self {
+        let buses = constraints.iter().enumerate()
+            .filter_map(|(i, &c)| c.map(|id| (i, id)))
+            .collect();
+        return Timetable {buses};
+    }
+
+    /// Each bus with how many minutes after the timestamp it has to leave
+    /// for the puzzle, which is its place in the schedule
+    pub fn schedule_offsets(&self) -> Vec<(u64, i64)> {
+        return self.buses.iter().map(|&(i, id)| (id, i as i64)).collect();
+    }
+
+    /// The first `count` departures at or after `time`, as timestamps with
+    /// bus IDs, soonest first
+    pub fn next_departures(&self, time: u64, count: usize) -> Vec<(u64, u64)> {
+        let mut upcoming: BinaryHeap<_> = self.buses.iter()
+            .map(|&(_, id)| Reverse((next_departure(id, time), id)))
+            .collect();
+        let mut departures = Vec::with_capacity(count);
+        while departures.len() < count {
+            let Reverse((departure, id)) = match upcoming.pop() {
+                Some(next) => next,
+                None => break,
+            };
+            departures.push((departure, id));
+            upcoming.push(Reverse((departure + id, id)));
+        }
+        return departures;
+    }
+
+    /// The buses leaving at exactly `time`
+    pub fn departing_at(&self, time: u64) -> Vec<u64> {
+        return self.buses.iter()
+            .map(|&(_, id)| id)
+            .filter(|&id| time % id == 0)
+            .collect();
+    }
+}
+
+/// When bus `id` next leaves at or after `time`. A bus leaving the moment
+/// someone gets to the stop can still be caught, so it counts.
+pub fn next_departure(id: u64, time: u64) -> u64 {
+    return time.div_ceil(id) * id;
+}
+
+/// The earliest timestamp at or after `from` where every bus leaves its
+/// offset's worth of minutes after it
+pub fn align(offsets: &[(u64, i64)], from: &BigUint) -> Result<BigUint, CrtError> {
+    let congruences: Vec<(i64, u64)> = offsets.iter().map(|&(id, offset)| (-offset, id)).collect();
+    let solution = crt::solve(&congruences)?;
+    if &solution.residue >= from {
+        return Ok(solution.residue);
+    }
+    let periods = (from - &solution.residue + &solution.modulus - 1u32) / &solution.modulus;
+    return Ok(solution.residue + periods * solution.modulus);
+}
+
+/// The next time at or after `from` that all the buses leave together
+pub fn next_together(bus_ids: &[u64], from: &BigUint) -> Result<BigUint, CrtError> {
+    let offsets: Vec<(u64, i64)> = bus_ids.iter().map(|&id| (id, 0)).collect();
+    return align(&offsets, from);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_timetable() -> Timetable {
+        let constraints = [Some(7), Some(13), None, None, Some(59), None, Some(31), Some(19)];
+        return Timetable::from_constraints(&constraints);
+    }
+
+    #[test]
+    fn test_next_departures() {
+        let timetable = get_timetable();
+        assert_eq!(timetable.next_departures(939, 4),
+            vec![(944, 59), (945, 7), (949, 13), (950, 19)]);
+        assert_eq!(timetable.next_departures(0, 3), vec![(0, 7), (0, 13), (0, 19)]);
+        let timetable = Timetable::from_constraints(&[Some(3)]);
+        assert_eq!(timetable.next_departures(4, 3), vec![(6, 3), (9, 3), (12, 3)]);
+        assert!(Timetable::from_constraints(&[None]).next_departures(4, 3).is_empty());
+    }
+
+    #[test]
+    fn test_next_departure() {
+        assert_eq!(next_departure(7, 939), 945);
+        assert_eq!(next_departure(7, 945), 945);
+        assert_eq!(next_departure(7, 0), 0);
+    }
+
+    #[test]
+    fn test_departing_at() {
+        let timetable = get_timetable();
+        assert_eq!(timetable.departing_at(91), vec![7, 13]);
+        assert_eq!(timetable.departing_at(92), Vec::<u64>::new());
+        assert_eq!(timetable.departing_at(0), vec![7, 13, 59, 31, 19]);
+    }
+
+    #[test]
+    fn test_align() {
+        let timetable = get_timetable();
+        let zero = BigUint::from(0u32);
+        assert_eq!(align(&timetable.schedule_offsets(), &zero), Ok(BigUint::from(1068781u32)));
+        let period = BigUint::from(7u64 * 13 * 59 * 31 * 19);
+        assert_eq!(align(&timetable.schedule_offsets(), &BigUint::from(1068782u32)),
+            Ok(BigUint::from(1068781u32) + period));
+        assert_eq!(align(&[(5, 2), (7, -1)], &zero), Ok(BigUint::from(8u32)));
+        assert_eq!(align(&[(4, 1), (6, 0)], &zero), Err(CrtError::NoSolution(1)));
+    }
+
+    #[test]
+    fn test_next_together() {
+        assert_eq!(next_together(&[7, 13], &BigUint::from(100u32)), Ok(BigUint::from(182u32)));
+        assert_eq!(next_together(&[4, 6], &BigUint::from(12u32)), Ok(BigUint::from(12u32)));
+        assert_eq!(next_together(&[4, 6], &BigUint::from(13u32)), Ok(BigUint::from(24u32)));
+    }
+}
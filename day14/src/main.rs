@@ -1,5 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::env;
 use std::io::{self, Read};
+use std::process;
 use lazy_static::lazy_static;
 use regex::Regex;
 
@@ -21,32 +23,127 @@ struct MaskV2 {
     mask1: u64,
 }
 
+/// A set of addresses: bits set in `floating` can be either value and the
+/// rest are as in `fixed`, which has the floating bits cleared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct AddressPattern {
+    fixed: u64,
+    floating: u64,
+}
+
+impl AddressPattern {
+    fn len(&self) -> u64 {
+        return 1 << self.floating.count_ones();
+    }
+
+    fn overlaps(&self, other: &AddressPattern) -> bool {
+        let both_fixed = !self.floating & !other.floating;
+        return (self.fixed ^ other.fixed) & both_fixed == 0;
+    }
+
+    /// The addresses in this pattern but not `other`, as patterns that don't
+    /// overlap each other. Each bit floating here but fixed in `other` gives
+    /// one piece: that bit set the other way, with the ones before it set to
+    /// match `other`.
+    fn subtract(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let mut pieces = Vec::new();
+        let mut remaining = *self;
+        let mut split_bits = self.floating & !other.floating;
+        while split_bits != 0 {
+            let bit = split_bits & split_bits.wrapping_neg();
+            split_bits &= !bit;
+            remaining.floating &= !bit;
+            pieces.push(AddressPattern {
+                fixed: remaining.fixed | (!other.fixed & bit),
+                floating: remaining.floating,
+            });
+            remaining.fixed |= other.fixed & bit;
+        }
+        return pieces;
+    }
+
+    /// Every address in the pattern. There are `len()` of them, so only for
+    /// patterns without many floating bits.
+    fn addresses(&self) -> impl Iterator<Item = u64> {
+        let AddressPattern {fixed, floating} = *self;
+        // Counts through every subset of the floating bits
+        let mut subset = Some(0u64);
+        return std::iter::from_fn(move || {
+            let current = subset?;
+            let next = current.wrapping_sub(floating) & floating;
+            subset = if next == 0 { None } else { Some(next) };
+            return Some(fixed | current);
+        });
+    }
+}
+
+/// Decoder v2 memory, kept as the patterns written to. A write takes its
+/// addresses out of every earlier pattern, so no two overlap and each
+/// address's value is found in at most one of them.
+#[derive(Debug, Default)]
+struct MemoryV2 {
+    writes: Vec<(AddressPattern, u64)>,
+}
+
+impl MemoryV2 {
+    fn write(&mut self, pattern: AddressPattern, value: u64) {
+        let mut writes = Vec::with_capacity(self.writes.len() + 1);
+        for &(existing, existing_value) in &self.writes {
+            for piece in existing.subtract(&pattern) {
+                writes.push((piece, existing_value));
+            }
+        }
+        // Zeroes add nothing to the sum, so only the overwriting matters
+        if value != 0 {
+            writes.push((pattern, value));
+        }
+        self.writes = writes;
+    }
+
+    /// The sum of every value in memory. Each of up to 2^36 addresses can
+    /// hold a full u64, so it's added up in a u128, which always fits.
+    fn sum(&self) -> u128 {
+        return self.writes.iter()
+            .map(|&(pattern, value)| value as u128 * pattern.len() as u128)
+            .sum();
+    }
+
+    /// How many addresses have a non-zero value
+    fn address_count(&self) -> u64 {
+        return self.writes.iter().map(|(pattern, _)| pattern.len()).sum();
+    }
+
+    /// Every address with a non-zero value
+    fn to_map(&self) -> HashMap<u64, u64> {
+        return self.writes.iter()
+            .flat_map(|&(pattern, value)| pattern.addresses().map(move |a| (a, value)))
+            .collect();
+    }
+}
+
 type Masks = (u64, u64);
 
 const NUM_BITS: usize = 36;
 
+/// Most addresses `--dump` will write out, since every one of them is
+/// expanded into memory first
+const MAX_DUMP_ADDRESSES: u64 = 1 << 20;
+
 fn apply_masks(value: u64, masks: Masks) -> u64 {
     let masked0 = value & !masks.0;
     let masked = masked0 | masks.1;
     return masked;
 }
 
-fn apply_v2_mask(address: u64, mask: &MaskV2) -> HashSet<u64> {
-    let bitmasked = address | mask.mask1;
-    let mut addrs = HashSet::new();
-
-    for i in &mask.floating {
-        if addrs.is_empty() {
-            addrs.insert(bitmasked & !(1 << i));
-            addrs.insert(bitmasked | (1 << i));
-        } else {
-            for addr in addrs.to_owned() {
-                addrs.insert(addr & !(1 << i));
-                addrs.insert(addr | (1 << i));
-            }
-        }
-    }
-    return addrs;
+fn apply_v2_mask(address: u64, mask: &MaskV2) -> AddressPattern {
+    let floating = mask.floating.iter().fold(0, |bits, i| bits | (1 << i));
+    return AddressPattern {
+        fixed: (address | mask.mask1) & !floating,
+        floating,
+    };
 }
 
 fn parse_mask(mask_str: &str) -> Masks {
@@ -154,18 +251,15 @@ fn run_program(program: &[Instruction]) -> HashMap<u64, u64> {
     return memory;
 }
 
-fn run_v2_program(program: &[InstructionV2]) -> HashMap<u64, u64> {
-    let mut memory = HashMap::new();
+fn run_v2_program(program: &[InstructionV2]) -> MemoryV2 {
+    let mut memory = MemoryV2::default();
     let mut mask = &MaskV2{floating: Vec::new(), mask1: 0};
 
     for instr in program {
         match instr {
             InstructionV2::Mask(m) => mask = m,
             InstructionV2::Mem(addr, val) => {
-                let addrs = apply_v2_mask(*addr, mask);
-                for a in addrs {
-                    memory.insert(a, *val);
-                }
+                memory.write(apply_v2_mask(*addr, mask), *val);
             }
         }
     }
@@ -180,8 +274,20 @@ fn part1(program: &[Instruction]) {
 
 fn part2(program: &[InstructionV2]) {
     let memory = run_v2_program(program);
-    let sum: u64 = memory.values().sum();
-    println!("Sum for part 2: {}", sum);
+    println!("Sum for part 2: {}", memory.sum());
+
+    if env::args().any(|a| a == "--dump") {
+        let count = memory.address_count();
+        if count > MAX_DUMP_ADDRESSES {
+            eprintln!("Too many addresses to dump: {} (at most {})", count, MAX_DUMP_ADDRESSES);
+            process::exit(1);
+        }
+        let mut addresses: Vec<_> = memory.to_map().into_iter().collect();
+        addresses.sort_unstable();
+        for (addr, val) in addresses {
+            println!("mem[{}] = {}", addr, val);
+        }
+    }
 }
 
 fn main() {
@@ -270,9 +376,12 @@ mem[8] = 0";
         let mask1 = 0b000000000000000000000000000000010010;
         let floating = vec![5, 0];
         let mask = MaskV2{mask1, floating};
-        let expected: HashSet<u64> = [26, 27, 58, 59].iter().cloned().collect();
+        let expected = AddressPattern {fixed: 26, floating: 0b100001};
         let actual = apply_v2_mask(42, &mask);
         assert_eq!(actual, expected);
+        let mut addresses: Vec<u64> = actual.addresses().collect();
+        addresses.sort_unstable();
+        assert_eq!(addresses, vec![26, 27, 58, 59]);
     }
 
     #[test]
@@ -323,6 +432,68 @@ mem[26] = 1";
         expected.insert(26, 1);
         expected.insert(27, 1);
         let actual = run_v2_program(&program);
-        assert_eq!(actual, expected);
+        assert_eq!(actual.to_map(), expected);
+        assert_eq!(actual.sum(), 208);
+    }
+
+    #[test]
+    fn test_subtract_pattern() {
+        let pattern = AddressPattern {fixed: 0b0000, floating: 0b1011};
+        let other = AddressPattern {fixed: 0b0010, floating: 0b1000};
+        let mut remaining: Vec<u64> = pattern.subtract(&other).iter()
+            .flat_map(|p| p.addresses())
+            .collect();
+        remaining.sort_unstable();
+        assert_eq!(remaining, vec![0b0000, 0b0001, 0b0011, 0b1000, 0b1001, 0b1011]);
+
+        let apart = AddressPattern {fixed: 0b0100, floating: 0b0011};
+        assert!(!pattern.overlaps(&apart));
+        assert_eq!(pattern.subtract(&apart), vec![pattern]);
+        assert!(pattern.subtract(&pattern).is_empty());
+    }
+
+    #[test]
+    fn test_memory_v2_matches_expansion() {
+        // Overlapping writes checked against writing every address out
+        let mut state = 12345u64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            return state >> 33;
+        };
+        for _ in 0..50 {
+            let mut memory = MemoryV2::default();
+            let mut expected: HashMap<u64, u64> = HashMap::new();
+            for _ in 0..20 {
+                let floating = next() & next() & 0xff;
+                let pattern = AddressPattern {fixed: next() & 0xff & !floating, floating};
+                let value = next() % 4;
+                memory.write(pattern, value);
+                for address in pattern.addresses() {
+                    expected.insert(address, value);
+                }
+            }
+            expected.retain(|_, &mut v| v != 0);
+            assert_eq!(memory.to_map(), expected);
+            assert_eq!(memory.sum(), expected.values().map(|&v| v as u128).sum());
+            assert_eq!(memory.address_count(), expected.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_memory_v2_many_floating_bits() {
+        let mut memory = MemoryV2::default();
+        memory.write(AddressPattern {fixed: 0, floating: (1 << NUM_BITS) - 1}, 3);
+        memory.write(AddressPattern {fixed: 1, floating: (1 << NUM_BITS) - 2}, 5);
+        memory.write(AddressPattern {fixed: 0b10, floating: 0}, 0);
+        let half = 1u64 << (NUM_BITS - 1);
+        assert_eq!(memory.sum(), (3 * (half - 1) + 5 * half) as u128);
+        assert_eq!(memory.address_count(), 2 * half - 1);
+    }
+
+    #[test]
+    fn test_memory_v2_large_sum() {
+        let mut memory = MemoryV2::default();
+        memory.write(AddressPattern {fixed: 0, floating: (1 << NUM_BITS) - 1}, u64::MAX);
+        assert_eq!(memory.sum(), u64::MAX as u128 * (1 << NUM_BITS));
     }
 }